@@ -0,0 +1,15 @@
+//! Writes the schema of the calculator's messages into `schema.json`.
+//! Run it on the host by `cargo run --example schema`.
+
+use calculator::contract::{ProcessMsg, QueryMsg, QueryRsp};
+use calculator::error::Error;
+use kelk_env::schema::ContractSchema;
+
+fn main() {
+    let schema = ContractSchema::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+        .process::<ProcessMsg>()
+        .query::<QueryMsg, QueryRsp>()
+        .error::<Error>();
+
+    std::fs::write("schema.json", schema.to_json()).expect("Writing schema failed");
+}
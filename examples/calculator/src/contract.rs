@@ -1,56 +1,48 @@
 use crate::error::Error;
-use crate::message::{ProcessMsg, QueryMsg, QueryRsp};
-use kelk_env::kelk_derive;
 use kelk_env::context::Context;
-
-fn add(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
-    ctx.storage
-        .write_i32(0, a + b)
-        .map_err(|_| Error::KelkError)
-}
-
-fn sub(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
-    ctx.storage
-        .write_i32(0, a - b)
-        .map_err(|_| Error::KelkError)
-}
-
-fn mul(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
-    ctx.storage
-        .write_i32(0, a * b)
-        .map_err(|_| Error::KelkError)
-}
-
-fn div(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
-    if b == 0 {
-        return Err(Error::DivByZero);
+use kelk_env::kelk_contract;
+
+/// The calculator contract.
+/// The process and query messages are generated by `kelk_contract`.
+pub struct Calculator;
+
+#[kelk_contract]
+impl Calculator {
+    #[process]
+    pub fn add(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
+        ctx.storage
+            .write_i32(0, a + b)
+            .map_err(|_| Error::KelkError)
     }
-    ctx.storage
-        .write_i32(0, a / b)
-        .map_err(|_| Error::KelkError)
-}
 
-fn query_result(ctx: Context) -> Result<i32, Error> {
-    ctx.storage.read_i32(0).map_err(|_| Error::KelkError)
-}
+    #[process]
+    pub fn sub(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
+        ctx.storage
+            .write_i32(0, a - b)
+            .map_err(|_| Error::KelkError)
+    }
 
-#[kelk_derive(process)]
-pub fn process(ctx: Context, msg: ProcessMsg) -> Result<(), Error> {
-    match msg {
-        ProcessMsg::Add { a, b } => add(ctx, a, b),
-        ProcessMsg::Sub { a, b } => sub(ctx, a, b),
-        ProcessMsg::Mul { a, b } => mul(ctx, a, b),
-        ProcessMsg::Div { a, b } => div(ctx, a, b),
+    #[process]
+    pub fn mul(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
+        ctx.storage
+            .write_i32(0, a * b)
+            .map_err(|_| Error::KelkError)
     }
-}
 
-#[kelk_derive(query)]
-pub fn query(ctx: Context, msg: QueryMsg) -> Result<QueryRsp, Error> {
-    let res = match msg {
-        QueryMsg::LastResult => query_result(ctx),
-    }?;
+    #[process]
+    pub fn div(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
+        if b == 0 {
+            return Err(Error::DivByZero);
+        }
+        ctx.storage
+            .write_i32(0, a / b)
+            .map_err(|_| Error::KelkError)
+    }
 
-    Ok(QueryRsp::Result { res })
+    #[query]
+    pub fn last_result(ctx: Context) -> Result<i32, Error> {
+        ctx.storage.read_i32(0).map_err(|_| Error::KelkError)
+    }
 }
 
 #[cfg(test)]
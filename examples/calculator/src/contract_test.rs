@@ -1,36 +1,78 @@
 use super::*;
+use kelk_env::envelope::Envelope;
 use kelk_env::mock::mock_context;
 
 #[test]
 fn test_add() {
     let ctx = mock_context(10);
-    add(ctx.as_ref(), 1, 2).unwrap();
-    let res = query_result(ctx.as_ref()).unwrap();
+    Calculator::add(ctx.as_ref(), 1, 2).unwrap();
+    let res = Calculator::last_result(ctx.as_ref()).unwrap();
     assert_eq!(res, 3);
 }
 
 #[test]
 fn test_sub() {
     let ctx = mock_context(10);
-    sub(ctx.as_ref(), 1, 2).unwrap();
-    let res = query_result(ctx.as_ref()).unwrap();
+    Calculator::sub(ctx.as_ref(), 1, 2).unwrap();
+    let res = Calculator::last_result(ctx.as_ref()).unwrap();
     assert_eq!(res, -1);
 }
 
 #[test]
 fn test_mul() {
     let ctx = mock_context(10);
-    mul(ctx.as_ref(), 2, 2).unwrap();
-    let res = query_result(ctx.as_ref()).unwrap();
+    Calculator::mul(ctx.as_ref(), 2, 2).unwrap();
+    let res = Calculator::last_result(ctx.as_ref()).unwrap();
     assert_eq!(res, 4);
 }
 
 #[test]
 fn test_div() {
     let ctx = mock_context(10);
-    div(ctx.as_ref(), 4, 2).unwrap();
-    let res = query_result(ctx.as_ref()).unwrap();
+    Calculator::div(ctx.as_ref(), 4, 2).unwrap();
+    let res = Calculator::last_result(ctx.as_ref()).unwrap();
     assert_eq!(res, 2);
 
-    assert!(div(ctx.as_ref(), 4, 0).is_err());
+    assert!(Calculator::div(ctx.as_ref(), 4, 0).is_err());
+}
+
+#[test]
+fn test_dispatch() {
+    let ctx = mock_context(10);
+    Calculator::dispatch_process(ctx.as_ref(), ProcessMsg::Mul { a: 3, b: 2 }).unwrap();
+    assert!(matches!(
+        Calculator::dispatch_query(ctx.as_ref(), QueryMsg::LastResult {}),
+        Ok(QueryRsp::LastResult(6))
+    ));
+}
+
+#[test]
+fn test_entry() {
+    let ctx = mock_context(10);
+    let res = kelk_env::entry::process(
+        &Calculator::dispatch_process,
+        ctx.as_ref(),
+        &[0x82, 0x02, 0x82, 0x03, 0x02], // ProcessMsg::Mul { a: 3, b: 2 }
+    );
+    assert_eq!(res, [0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]); // Ok(Response::default())
+
+    let res = kelk_env::entry::query(
+        &Calculator::dispatch_query,
+        ctx.as_ref(),
+        &[0x82, 0x00, 0x80], // QueryMsg::LastResult {}
+    );
+    assert_eq!(res, [0x82, 0x00, 0x82, 0x00, 0x81, 0x06]); // Ok(QueryRsp::LastResult(6))
+    let res: Envelope<QueryRsp, Error> = minicbor::decode(&res).unwrap();
+    assert!(matches!(res, Envelope::Ok(QueryRsp::LastResult(6))));
+}
+
+#[test]
+fn test_query_schema() {
+    // The schema of the query results should match their encoding in `test_entry`.
+    assert_eq!(
+        kelk_env::schema::ContractSchema::new("calculator", "0.1.0")
+            .query::<QueryMsg, QueryRsp>()
+            .to_json(),
+        r#"{"name":"calculator","version":"0.1.0","instantiate":null,"process":null,"query":{"enum":"QueryMsg","variants":[{"name":"LastResult","tag":0,"fields":[]}]},"response":{"enum":"QueryRsp","variants":[{"name":"LastResult","tag":0,"fields":[{"tag":0,"type":"i32"}]}]},"migrate":null,"error":null}"#
+    );
 }
@@ -1,6 +1,7 @@
-use minicbor::Encode;
+use kelk_env::Schema;
+use minicbor::{Decode, Encode};
 
-#[derive(Clone, Debug, Encode)]
+#[derive(Clone, Debug, Encode, Decode, Schema)]
 pub enum Error {
     #[n(0)]
     KelkError,
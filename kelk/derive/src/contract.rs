@@ -0,0 +1,390 @@
+//! Code generation for the `#[kelk_contract]` attribute
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    Attribute, FnArg, GenericArgument, Ident, ImplItem, ImplItemMethod, ItemImpl, Lit, Meta,
+    NestedMeta, Pat, PathArguments, ReturnType, Type,
+};
+
+/// The kind of a contract method
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Process,
+    Query,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Process => "process",
+            Kind::Query => "query",
+        }
+    }
+}
+
+/// A method that is marked with `#[process]` or `#[query]`
+struct Method {
+    kind: Kind,
+    tag: u32,
+    ident: Ident,
+    variant: Ident,
+    params: Vec<(Ident, Type)>,
+    ok_type: Type,
+    err_type: Type,
+}
+
+pub(crate) fn expand(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`kelk_contract` can't be used on trait implementations",
+        ));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &item.generics,
+            "`kelk_contract` can't be used on generic types",
+        ));
+    }
+
+    let mut methods = Vec::new();
+    // The next tag is `None` after the method with the tag `u32::MAX`.
+    let mut next_tags = [Some(0u32); 2];
+    for impl_item in item.items.iter_mut() {
+        if let ImplItem::Method(method) = impl_item {
+            if let Some((kind, tag)) = take_kind(&mut method.attrs)? {
+                let next_tag = &mut next_tags[kind as usize];
+                let tag = match tag.or(*next_tag) {
+                    Some(tag) => tag,
+                    None => {
+                        return Err(syn::Error::new_spanned(
+                            &method.sig.ident,
+                            "the tag overflows `u32`, set the tag explicitly",
+                        ))
+                    }
+                };
+                if let Some(other) = methods
+                    .iter()
+                    .find(|m: &&Method| m.kind == kind && m.tag == tag)
+                {
+                    return Err(syn::Error::new_spanned(
+                        &method.sig.ident,
+                        format!("tag {} is already used by `{}`", tag, other.ident),
+                    ));
+                }
+                *next_tag = tag.checked_add(1);
+                methods.push(parse_method(kind, tag, method)?);
+            }
+        }
+    }
+
+    check_error_types(&methods)?;
+
+    let self_ty = &item.self_ty;
+    let process = expand_kind(Kind::Process, self_ty, &methods);
+    let query = expand_kind(Kind::Query, self_ty, &methods);
+    let metadata = crate::contract_metadata_entries();
+
+    Ok(quote!(
+        #item
+        #process
+        #query
+
+        #[cfg(target_arch = "wasm32")]
+        mod __wasm_contract_metadata {
+            #metadata
+        }
+    ))
+}
+
+/// removes the `#[process]` or `#[query]` attribute of the method and
+/// returns its kind and the optional tag.
+fn take_kind(attrs: &mut Vec<Attribute>) -> syn::Result<Option<(Kind, Option<u32>)>> {
+    let pos = attrs.iter().position(|attr| {
+        attr.path.is_ident(Kind::Process.name()) || attr.path.is_ident(Kind::Query.name())
+    });
+    let attr = match pos {
+        Some(pos) => attrs.remove(pos),
+        None => return Ok(None),
+    };
+    let kind = if attr.path.is_ident(Kind::Process.name()) {
+        Kind::Process
+    } else {
+        Kind::Query
+    };
+
+    match attr.parse_meta()? {
+        Meta::Path(_) => Ok(Some((kind, None))),
+        Meta::List(list) if list.nested.len() == 1 => match &list.nested[0] {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("tag") => match &nv.lit {
+                Lit::Int(lit) => Ok(Some((kind, Some(lit.base10_parse()?)))),
+                lit => Err(syn::Error::new_spanned(lit, "the tag should be an integer")),
+            },
+            nested => Err(syn::Error::new_spanned(nested, "expected `tag = <number>`")),
+        },
+        meta => Err(syn::Error::new_spanned(meta, "expected `tag = <number>`")),
+    }
+}
+
+fn parse_method(kind: Kind, tag: u32, method: &ImplItemMethod) -> syn::Result<Method> {
+    let sig = &method.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "contract methods can't be async",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "contract methods can't be generic",
+        ));
+    }
+
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Typed(pat)) if is_type(&pat.ty, "Context") => {}
+        Some(FnArg::Receiver(recv)) => {
+            return Err(syn::Error::new_spanned(
+                recv,
+                "contract methods can't take `self`",
+            ))
+        }
+        Some(arg) => {
+            return Err(syn::Error::new_spanned(
+                arg,
+                "the first parameter should be `Context`",
+            ))
+        }
+        None => {
+            return Err(syn::Error::new(
+                sig.paren_token.span,
+                "the first parameter should be `Context`",
+            ))
+        }
+    }
+
+    let params = inputs
+        .map(|arg| match arg {
+            FnArg::Typed(pat) => match &*pat.pat {
+                Pat::Ident(ident) => Ok((ident.ident.clone(), (*pat.ty).clone())),
+                pat => Err(syn::Error::new_spanned(
+                    pat,
+                    "parameters of contract methods should be identifiers",
+                )),
+            },
+            FnArg::Receiver(recv) => Err(syn::Error::new_spanned(
+                recv,
+                "contract methods can't take `self`",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let (ok_type, err_type) = result_types(&sig.output).ok_or_else(|| match &sig.output {
+        ReturnType::Type(_, ty) => syn::Error::new_spanned(ty, "expected `Result<T, E>`"),
+        ReturnType::Default => syn::Error::new_spanned(sig, "expected `Result<T, E>`"),
+    })?;
+
+    Ok(Method {
+        kind,
+        tag,
+        ident: sig.ident.clone(),
+        variant: Ident::new(&camel_case(&sig.ident.to_string()), sig.ident.span()),
+        params,
+        ok_type,
+        err_type,
+    })
+}
+
+/// checks that the methods of each kind return the same error type as the first one.
+/// The types are compared as they are written.
+fn check_error_types(methods: &[Method]) -> syn::Result<()> {
+    for kind in [Kind::Process, Kind::Query] {
+        let mut methods = methods.iter().filter(|m| m.kind == kind);
+        let first = match methods.next() {
+            Some(first) => first,
+            None => continue,
+        };
+        let expected = first.err_type.to_token_stream().to_string();
+        if let Some(method) = methods.find(|m| m.err_type.to_token_stream().to_string() != expected)
+        {
+            return Err(syn::Error::new_spanned(
+                &method.err_type,
+                format!(
+                    "the {} methods should return the same error type as `{}`",
+                    kind.name(),
+                    first.ident
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// generates the message enum, the dispatcher, the wasm export and its metadata entry
+/// for the given kind
+fn expand_kind(kind: Kind, self_ty: &Type, methods: &[Method]) -> TokenStream {
+    let methods: Vec<&Method> = methods.iter().filter(|m| m.kind == kind).collect();
+    let first = match methods.first() {
+        Some(first) => first,
+        None => return quote!(),
+    };
+
+    let msg_enum = match kind {
+        Kind::Process => format_ident!("ProcessMsg"),
+        Kind::Query => format_ident!("QueryMsg"),
+    };
+    let dispatcher = format_ident!("dispatch_{}", kind.name());
+    let export = format_ident!("{}", kind.name());
+    let do_fn = format_ident!("do_{}", kind.name());
+    let module = format_ident!("__wasm_export_{}", kind.name());
+    let err_type = &first.err_type;
+    let metadata = crate::export_metadata_entry(kind.name());
+
+    let variants = methods.iter().map(|m| {
+        let tag = Literal::u32_unsuffixed(m.tag);
+        let variant = &m.variant;
+        let fields = m.params.iter().enumerate().map(|(i, (ident, ty))| {
+            let n = Literal::usize_unsuffixed(i);
+            quote!(#[n(#n)] #ident: #ty)
+        });
+        quote!(#[n(#tag)] #variant { #(#fields),* })
+    });
+
+    let (rsp_type, rsp_def, map_ok) = match kind {
+        Kind::Process => (
+            quote!(kelk_env::response::Response),
+            quote!(),
+            methods
+                .iter()
+                .map(|_| quote!(::core::convert::Into::into))
+                .collect::<Vec<_>>(),
+        ),
+        Kind::Query => {
+            let rsp_variants = methods.iter().map(|m| {
+                let tag = Literal::u32_unsuffixed(m.tag);
+                let variant = &m.variant;
+                let ty = &m.ok_type;
+                quote!(#[n(#tag)] #variant(#[n(0)] #ty))
+            });
+            (
+                quote!(QueryRsp),
+                quote!(
+                    /// The response of the queries, generated by `kelk_contract`.
+                    /// It has a variant for each query with the same tag.
+                    #[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, kelk_env::Schema)]
+                    pub enum QueryRsp {
+                        #(#rsp_variants),*
+                    }
+                ),
+                methods
+                    .iter()
+                    .map(|m| {
+                        let variant = &m.variant;
+                        quote!(QueryRsp::#variant)
+                    })
+                    .collect(),
+            )
+        }
+    };
+
+    let arms = methods.iter().zip(map_ok).map(|(m, map_ok)| {
+        let ident = &m.ident;
+        let variant = &m.variant;
+        let names: Vec<&Ident> = m.params.iter().map(|(ident, _)| ident).collect();
+        quote!(
+            #msg_enum::#variant { #(#names),* } => #self_ty::#ident(ctx, #(#names),*)
+                .map(#map_ok)
+        )
+    });
+
+    let doc = format!(
+        "The {} messages of the contract, generated by `kelk_contract`.",
+        kind.name()
+    );
+    let dispatcher_doc = format!(
+        "dispatches the {} message to the contract method.",
+        kind.name()
+    );
+
+    quote!(
+        #[doc = #doc]
+        #[derive(Clone, Debug, minicbor::Encode, minicbor::Decode, kelk_env::Schema)]
+        pub enum #msg_enum {
+            #(#variants),*
+        }
+
+        #rsp_def
+
+        impl #self_ty {
+            #[doc = #dispatcher_doc]
+            pub fn #dispatcher(
+                ctx: kelk_env::context::Context,
+                msg: #msg_enum,
+            ) -> ::core::result::Result<#rsp_type, #err_type> {
+                match msg {
+                    #(#arms),*
+                }
+            }
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        mod #module {
+            #[no_mangle]
+            extern "C" fn #export(msg_ptr: u64) -> u64 {
+                kelk_env::#do_fn(&super::#self_ty::#dispatcher, msg_ptr)
+            }
+
+            #metadata
+        }
+    )
+}
+
+/// returns the `T` and `E` types of `Result<T, E>`
+fn result_types(output: &ReturnType) -> Option<(Type, Type)> {
+    let ty = match output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => return None,
+    };
+    let seg = match &**ty {
+        Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if seg.ident != "Result" {
+        return None;
+    }
+    let args = match &seg.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 2 => args,
+        _ => return None,
+    };
+    match (&args.args[0], &args.args[1]) {
+        (GenericArgument::Type(ok), GenericArgument::Type(err)) => Some((ok.clone(), err.clone())),
+        _ => None,
+    }
+}
+
+/// checks if the last segment of the type path is `name`
+pub(crate) fn is_type(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|seg| seg.ident == name),
+        _ => false,
+    }
+}
+
+/// converts `snake_case` to `CamelCase`
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -1,15 +1,28 @@
-use std::str::FromStr;
-
 use proc_macro::TokenStream;
-use syn::parse_macro_input;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, DeriveInput, FnArg, Ident, ItemFn, ItemImpl, ReturnType};
+
+mod contract;
+mod schema;
+
+use contract::is_type;
 
 /// The attribute macro to inject the code at the beginning of entry functions
 /// for the Wasm contract actor.
 ///
-/// It can be added to the contract's instantiate, process and query functions
+/// It can be added to the contract's instantiate, process, query and migrate functions
 /// like this:
 /// ```
 /// use kelk_derive::kelk_derive;
+/// use kelk_env::context::Context;
+/// use kelk_env::metadata::ContractVersion;
+/// # type InstantiateMsg = u32;
+/// # type ProcessMsg = u32;
+/// # type QueryMsg = u32;
+/// # type MigrateMsg = u32;
+/// # type Error = u32;
 ///
 /// #[kelk_derive(instantiate)]
 /// pub fn instantiate(ctx: Context, msg: InstantiateMsg) -> Result<(), Error> {
@@ -19,35 +32,339 @@ use syn::parse_macro_input;
 /// #[kelk_derive(process)]
 /// pub fn process(ctx: Context, msg: ProcessMsg) -> Result<(), Error> {
 ///   todo!()
-/// #}
+/// }
 ///
 /// #[kelk_derive(query)]
 /// pub fn query(ctx: Context, msg: QueryMsg) -> Result<(), Error> {
 ///   todo!()
 /// }
+///
+/// #[kelk_derive(migrate)]
+/// pub fn migrate(ctx: Context, old: Option<ContractVersion>, msg: MigrateMsg) -> Result<(), Error> {
+///   todo!()
+/// }
+/// # fn main() {}
 /// ```
 ///
-/// where `InstantiateMsg`, `ProcessMsg`, and `QueryMsg` are contract defined
+/// where `InstantiateMsg`, `ProcessMsg`, `QueryMsg` and `MigrateMsg` are contract defined
 /// types that implement CBOR encoding.
+///
+/// The attribute argument defines the entry point, so the function can have any name.
+///
+/// The instantiate and migrate entries store the package name and version
+/// of the contract crate in the contract metadata.
+///
+/// The exported entry point and the package name and version are also put into
+/// the `kelk_metadata` custom section of the wasm file.
+#[proc_macro_attribute]
+pub fn kelk_derive(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as ItemFn);
+    let expanded = match expand(attr.into(), &function) {
+        Ok(entry) => entry,
+        Err(err) => err.to_compile_error(),
+    };
+
+    quote!(
+        #function
+        #expanded
+    )
+    .into()
+}
+
+/// The attribute macro to generate the messages and the entry points of a contract
+/// from an `impl` block.
+///
+/// The methods marked with `#[process]` or `#[query]` become the variants of
+/// the `ProcessMsg` or `QueryMsg` enums, like this:
+/// ```
+/// use kelk_derive::kelk_contract;
+/// use kelk_env::context::Context;
+/// # type Error = u32;
+///
+/// pub struct Calculator;
+///
+/// #[kelk_contract]
+/// impl Calculator {
+///     #[process]
+///     pub fn add(ctx: Context, a: i32, b: i32) -> Result<(), Error> {
+///         todo!()
+///     }
+///
+///     #[query]
+///     pub fn last_result(ctx: Context) -> Result<i32, Error> {
+///         todo!()
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// generates:
+/// ```text
+/// pub enum ProcessMsg {
+///     #[n(0)]
+///     Add {
+///         #[n(0)]
+///         a: i32,
+///         #[n(1)]
+///         b: i32,
+///     },
+/// }
+///
+/// pub enum QueryMsg {
+///     #[n(0)]
+///     LastResult {},
+/// }
+///
+/// pub enum QueryRsp {
+///     #[n(0)]
+///     LastResult(#[n(0)] i32),
+/// }
+/// ```
+///
+/// together with `Calculator::dispatch_process` and `Calculator::dispatch_query`
+/// and the `process` and `query` exports for the Wasm target.
+/// The response of a query is the `QueryRsp` enum, which has a variant for each query
+/// with the same tag, therefore the methods can return different types.
+///
+/// Methods take `Context` as the first parameter and the message fields after that.
+/// They return a `Result`, and the methods of each kind should have the same error type,
+/// written the same way.
+///
+/// The tags are assigned in the order of the methods, starting from zero for each kind.
+/// New methods should be added at the end to keep the tags stable.
+/// A tag can be set explicitly like `#[process(tag = 5)]`,
+/// and the methods after that continue from it.
+///
+/// The exports and the package name and version of the contract crate are put into
+/// the `kelk_metadata` custom section of the wasm file.
+///
+/// The messages and the response derive `Schema`, `Encode` and `Decode`,
+/// so the methods' parameters and results should implement them.
+///
+/// The contract crate should depend on `minicbor` for encoding the messages.
 #[proc_macro_attribute]
-pub fn kelk_derive(_attr: TokenStream, mut item: TokenStream) -> TokenStream {
-    let cloned = item.clone();
-    let function = parse_macro_input!(cloned as syn::ItemFn);
-    let name = function.sig.ident.to_string();
+pub fn kelk_contract(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            proc_macro2::TokenStream::from(attr)
+                .into_iter()
+                .next()
+                .unwrap()
+                .span(),
+            "`kelk_contract` doesn't take any argument",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let item = parse_macro_input!(item as ItemImpl);
+    match contract::expand(item) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Derives `kelk_env::schema::Schema` for the types that are encoded by `minicbor`.
+///
+/// The tags are taken from the `#[n(..)]`, `#[b(..)]` and `#[cbor(n(..))]` attributes,
+/// and the fields encoded `with = "minicbor::bytes"` are described as bytes:
+/// ```
+/// use kelk_env::address::Address;
+/// use kelk_env::Schema;
+/// use minicbor::{Decode, Encode};
+///
+/// #[derive(Encode, Decode, Schema)]
+/// pub enum QueryMsg {
+///     #[n(0)]
+///     Balance {
+///         #[n(0)]
+///         addr: Address,
+///     },
+/// }
+/// ```
+#[proc_macro_derive(Schema, attributes(n, b, cbor))]
+pub fn derive_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match schema::expand(input) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// The entry points that a contract can export
+enum Entry {
+    Instantiate,
+    Process,
+    Query,
+    Migrate,
+}
+
+impl Entry {
+    fn parse(attr: proc_macro2::TokenStream) -> syn::Result<Self> {
+        let ident: Ident = syn::parse2(attr).map_err(|err| {
+            syn::Error::new(
+                err.span(),
+                "expected one of `instantiate`, `process`, `query` or `migrate`",
+            )
+        })?;
+        match ident.to_string().as_str() {
+            "instantiate" => Ok(Entry::Instantiate),
+            "process" => Ok(Entry::Process),
+            "query" => Ok(Entry::Query),
+            "migrate" => Ok(Entry::Migrate),
+            _ => Err(syn::Error::new(
+                ident.span(),
+                "expected one of `instantiate`, `process`, `query` or `migrate`",
+            )),
+        }
+    }
 
-    let new_code = format!(
-        r##"
+    fn name(&self) -> &'static str {
+        match self {
+            Entry::Instantiate => "instantiate",
+            Entry::Process => "process",
+            Entry::Query => "query",
+            Entry::Migrate => "migrate",
+        }
+    }
+
+    /// the parameters that the entry function should have
+    fn params(&self) -> &'static str {
+        match self {
+            Entry::Migrate => "`ctx: Context, old: Option<ContractVersion>, msg: MigrateMsg`",
+            _ => "`ctx: Context, msg: Msg`",
+        }
+    }
+}
+
+fn expand(
+    attr: proc_macro2::TokenStream,
+    function: &ItemFn,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let entry = Entry::parse(attr)?;
+    check_signature(&entry, function)?;
+
+    let func = &function.sig.ident;
+    let export = Ident::new(entry.name(), Span::call_site());
+    let dispatcher = format_ident!("do_{}", entry.name());
+    let module = format_ident!("__wasm_export_{}", func);
+    let version_args = match entry {
+        Entry::Instantiate | Entry::Migrate => {
+            quote!(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"),)
+        }
+        Entry::Process | Entry::Query => quote!(),
+    };
+
+    let export_entry = export_metadata_entry(entry.name());
+    let contract_entries = contract_metadata_entries();
+
+    Ok(quote!(
         #[cfg(target_arch = "wasm32")]
-        mod __wasm_export_{name} {{
+        mod #module {
             #[no_mangle]
-            extern "C" fn {name}(msg_ptr: u64) -> u64 {{
-                kelk_env::do_{name}(&super::{name}, msg_ptr)
-            }}
-        }}
-    "##,
-        name = name,
-    );
-    let entry = TokenStream::from_str(&new_code).unwrap();
-    item.extend(entry);
-    item
+            extern "C" fn #export(msg_ptr: u64) -> u64 {
+                kelk_env::#dispatcher(&super::#func, #version_args msg_ptr)
+            }
+
+            #export_entry
+            #contract_entries
+        }
+    ))
+}
+
+/// generates the entry of the wasm metadata section for the exported entry point
+pub(crate) fn export_metadata_entry(export: &str) -> proc_macro2::TokenStream {
+    let export = syn::LitByteStr::new(export.as_bytes(), Span::call_site());
+    quote!(
+        kelk_env::__wasm_metadata_entry!(
+            EXPORT_ENTRY,
+            kelk_env::wasm_metadata::KEY_EXPORT,
+            #export
+        );
+    )
+}
+
+/// generates the contract name and version entries of the wasm metadata section
+pub(crate) fn contract_metadata_entries() -> proc_macro2::TokenStream {
+    quote!(
+        kelk_env::__wasm_metadata_entry!(
+            CONTRACT_NAME_ENTRY,
+            kelk_env::wasm_metadata::KEY_CONTRACT_NAME,
+            env!("CARGO_PKG_NAME").as_bytes()
+        );
+        kelk_env::__wasm_metadata_entry!(
+            CONTRACT_VERSION_ENTRY,
+            kelk_env::wasm_metadata::KEY_CONTRACT_VERSION,
+            env!("CARGO_PKG_VERSION").as_bytes()
+        );
+    )
+}
+
+fn check_signature(entry: &Entry, function: &ItemFn) -> syn::Result<()> {
+    let sig = &function.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "entry functions can't be async",
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "entry functions can't be generic",
+        ));
+    }
+
+    let expected = match entry {
+        Entry::Migrate => 3,
+        _ => 2,
+    };
+    if sig.inputs.len() != expected {
+        return Err(syn::Error::new(
+            sig.paren_token.span,
+            format!(
+                "`{}` entry function should have these parameters: {}",
+                entry.name(),
+                entry.params()
+            ),
+        ));
+    }
+
+    let types = sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            FnArg::Typed(pat) => Ok(&*pat.ty),
+            FnArg::Receiver(recv) => Err(syn::Error::new_spanned(
+                recv,
+                "entry functions can't take `self`",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    if !is_type(types[0], "Context") {
+        return Err(syn::Error::new_spanned(
+            types[0],
+            "the first parameter should be `Context`",
+        ));
+    }
+    if let Entry::Migrate = entry {
+        if !is_type(types[1], "Option") {
+            return Err(syn::Error::new_spanned(
+                types[1],
+                "the second parameter should be `Option<ContractVersion>`",
+            ));
+        }
+    }
+
+    match &sig.output {
+        ReturnType::Type(_, ty) if is_type(ty, "Result") => Ok(()),
+        ReturnType::Type(_, ty) => Err(syn::Error::new_spanned(
+            ty,
+            "entry functions should return a `Result`",
+        )),
+        ReturnType::Default => Err(syn::Error::new(
+            sig.span(),
+            "entry functions should return a `Result`",
+        )),
+    }
 }
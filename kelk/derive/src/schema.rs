@@ -0,0 +1,132 @@
+//! Code generation for `#[derive(Schema)]`
+
+use crate::contract::is_type;
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{parse_quote, Attribute, Data, DeriveInput, Fields, Lit, LitInt, Meta, NestedMeta, Type};
+
+pub(crate) fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let name = ident.to_string();
+    let schema = match &input.data {
+        Data::Struct(data) => {
+            let fields = expand_fields(&data.fields)?;
+            quote!(kelk_env::schema::SchemaType::Struct {
+                name: #name,
+                fields: #fields,
+            })
+        }
+        Data::Enum(data) => {
+            let variants = data
+                .variants
+                .iter()
+                .map(|variant| {
+                    let (tag, _) = parse_tag(&variant.attrs)?;
+                    let tag = tag.ok_or_else(|| {
+                        syn::Error::new_spanned(variant, "missing `#[n(..)]` tag")
+                    })?;
+                    let name = variant.ident.to_string();
+                    let fields = expand_fields(&variant.fields)?;
+                    Ok(quote!(kelk_env::schema::Variant {
+                        name: #name,
+                        tag: #tag,
+                        fields: #fields,
+                    }))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(kelk_env::schema::SchemaType::Enum {
+                name: #name,
+                variants: kelk_env::alloc::vec![#(#variants),*],
+            })
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "`Schema` can't be derived for unions",
+            ))
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(kelk_env::schema::Schema));
+    }
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote!(
+        impl #impl_generics kelk_env::schema::Schema for #ident #ty_generics #where_clause {
+            fn schema() -> kelk_env::schema::SchemaType {
+                #schema
+            }
+        }
+    ))
+}
+
+fn expand_fields(fields: &Fields) -> syn::Result<TokenStream> {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let (tag, bytes) = parse_tag(&field.attrs)?;
+            let tag =
+                tag.ok_or_else(|| syn::Error::new_spanned(field, "missing `#[n(..)]` tag"))?;
+            let name = match &field.ident {
+                Some(ident) => {
+                    let name = ident.to_string();
+                    quote!(::core::option::Option::Some(#name))
+                }
+                None => quote!(::core::option::Option::None),
+            };
+            let ty = expand_type(&field.ty, bytes);
+            Ok(quote!(kelk_env::schema::Field {
+                name: #name,
+                tag: #tag,
+                ty: #ty,
+            }))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote!(kelk_env::alloc::vec![#(#fields),*]))
+}
+
+fn expand_type(ty: &Type, bytes: bool) -> TokenStream {
+    let bytes_schema = quote!(kelk_env::schema::SchemaType::Primitive("bytes"));
+    match (bytes, is_type(ty, "Option")) {
+        (true, true) => quote!(kelk_env::schema::SchemaType::Option(
+            kelk_env::alloc::boxed::Box::new(#bytes_schema)
+        )),
+        (true, false) => bytes_schema,
+        (false, _) => quote!(<#ty as kelk_env::schema::Schema>::schema()),
+    }
+}
+
+/// finds the CBOR tag in `#[n(..)]`, `#[b(..)]` or `#[cbor(n(..))]` attributes.
+/// It also returns true if the field is encoded with `minicbor::bytes`.
+fn parse_tag(attrs: &[Attribute]) -> syn::Result<(Option<u32>, bool)> {
+    let mut tag = None;
+    let mut bytes = false;
+    for attr in attrs {
+        if attr.path.is_ident("n") || attr.path.is_ident("b") {
+            tag = Some(attr.parse_args::<LitInt>()?.base10_parse()?);
+        } else if attr.path.is_ident("cbor") {
+            if let Meta::List(list) = attr.parse_meta()? {
+                for nested in list.nested.iter() {
+                    match nested {
+                        NestedMeta::Meta(Meta::List(inner))
+                            if inner.path.is_ident("n") || inner.path.is_ident("b") =>
+                        {
+                            if let Some(NestedMeta::Lit(Lit::Int(lit))) = inner.nested.first() {
+                                tag = Some(lit.base10_parse()?);
+                            }
+                        }
+                        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("with") => {
+                            if let Lit::Str(lit) = &nv.lit {
+                                bytes = lit.value() == "minicbor::bytes";
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    Ok((tag, bytes))
+}
@@ -0,0 +1,10 @@
+//! Checks the code that the macros accept and the errors of the code that they reject.
+//! The expected errors are in the `.stderr` files next to the tests,
+//! and they can be updated by running the tests with `TRYBUILD=overwrite`.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
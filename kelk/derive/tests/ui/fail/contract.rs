@@ -0,0 +1,73 @@
+use kelk_env::kelk_contract;
+
+pub struct NoContext;
+
+#[kelk_contract]
+impl NoContext {
+    #[process]
+    pub fn add(a: i32, b: i32) -> Result<(), u32> {
+        Ok(())
+    }
+}
+
+pub struct NoResult;
+
+#[kelk_contract]
+impl NoResult {
+    #[query]
+    pub fn value(_ctx: kelk_env::context::Context) -> u32 {
+        0
+    }
+}
+
+pub struct SameTag;
+
+#[kelk_contract]
+impl SameTag {
+    #[process]
+    pub fn first(_ctx: kelk_env::context::Context) -> Result<(), u32> {
+        Ok(())
+    }
+
+    #[process(tag = 0)]
+    pub fn second(_ctx: kelk_env::context::Context) -> Result<(), u32> {
+        Ok(())
+    }
+}
+
+pub struct OtherError;
+
+#[kelk_contract]
+impl OtherError {
+    #[process]
+    pub fn first(_ctx: kelk_env::context::Context) -> Result<(), u32> {
+        Ok(())
+    }
+
+    #[process]
+    pub fn second(_ctx: kelk_env::context::Context) -> Result<(), i32> {
+        Ok(())
+    }
+}
+
+pub struct TagOverflow;
+
+#[kelk_contract]
+impl TagOverflow {
+    #[query(tag = 4294967295)]
+    pub fn first(_ctx: kelk_env::context::Context) -> Result<u32, u32> {
+        Ok(0)
+    }
+
+    #[query]
+    pub fn second(_ctx: kelk_env::context::Context) -> Result<u32, u32> {
+        Ok(0)
+    }
+}
+
+pub struct WithArgument;
+
+#[kelk_contract(process)]
+impl WithArgument {}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use kelk_env::context::Context;
+use kelk_env::kelk_derive;
+
+#[kelk_derive(execute)]
+pub fn execute(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive]
+pub fn process(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(process, query)]
+pub fn query(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+fn main() {}
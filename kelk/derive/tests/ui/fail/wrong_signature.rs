@@ -0,0 +1,43 @@
+use kelk_env::context::Context;
+use kelk_env::kelk_derive;
+use kelk_env::metadata::ContractVersion;
+
+#[kelk_derive(instantiate)]
+pub fn instantiate(_msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(process)]
+pub fn process(_msg: u32, _ctx: Context) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(query)]
+pub fn query(_ctx: Context, msg: u32) -> u32 {
+    msg
+}
+
+#[kelk_derive(query)]
+pub fn query_default(_ctx: Context, _msg: u32) {}
+
+#[kelk_derive(migrate)]
+pub fn migrate(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(migrate)]
+pub fn migrate_version(_ctx: Context, _old: ContractVersion, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(process)]
+pub async fn process_async(_ctx: Context<'_>, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(process)]
+pub fn process_generic<T>(_ctx: Context, _msg: T) -> Result<(), u32> {
+    Ok(())
+}
+
+fn main() {}
@@ -0,0 +1,40 @@
+use kelk_env::context::Context;
+use kelk_env::kelk_contract;
+
+pub struct Counter;
+
+#[kelk_contract]
+impl Counter {
+    #[process]
+    pub fn increase(_ctx: Context, by: u32) -> Result<(), u32> {
+        match by {
+            0 => Err(1),
+            _ => Ok(()),
+        }
+    }
+
+    #[process(tag = 5)]
+    pub fn reset(_ctx: Context) -> Result<(), u32> {
+        Ok(())
+    }
+
+    #[query]
+    pub fn value(_ctx: Context) -> Result<u32, u32> {
+        Ok(0)
+    }
+
+    #[query]
+    pub fn name(_ctx: Context) -> Result<String, u32> {
+        Ok("counter".into())
+    }
+
+    pub fn helper() {}
+}
+
+fn main() {
+    let _ = ProcessMsg::Increase { by: 1 };
+    let _ = ProcessMsg::Reset {};
+    let _ = QueryMsg::Value {};
+    let _ = QueryMsg::Name {};
+    Counter::helper();
+}
@@ -0,0 +1,25 @@
+use kelk_env::context::Context;
+use kelk_env::kelk_derive;
+use kelk_env::metadata::ContractVersion;
+
+#[kelk_derive(instantiate)]
+pub fn instantiate(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(process)]
+pub fn execute(_ctx: Context, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+#[kelk_derive(query)]
+pub fn query(_ctx: Context, msg: u32) -> Result<u32, u32> {
+    Ok(msg)
+}
+
+#[kelk_derive(migrate)]
+pub fn migrate(_ctx: Context, _old: Option<ContractVersion>, _msg: u32) -> Result<(), u32> {
+    Ok(())
+}
+
+fn main() {}
@@ -0,0 +1,73 @@
+//! Address type for representing actor address
+
+use crate::error::Error;
+use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
+
+/// The size of the address in bytes
+pub const ADDRESS_SIZE: usize = 21;
+
+/// Address type in Zarb blockchain
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Address([u8; ADDRESS_SIZE]);
+
+impl Address {
+    /// creates an address from the given bytes.
+    /// It returns an error if the length of data is not equal to `ADDRESS_SIZE`.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let data = buf
+            .try_into()
+            .map_err(|_| Error::GenericError("invalid address length"))?;
+        Ok(Self(data))
+    }
+
+    /// returns the address as bytes
+    pub fn as_bytes(&self) -> &[u8; ADDRESS_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; ADDRESS_SIZE]> for Address {
+    fn from(data: [u8; ADDRESS_SIZE]) -> Self {
+        Self(data)
+    }
+}
+
+impl Encode for Address {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.bytes(self.0.as_ref())?.ok()
+    }
+}
+
+impl<'b> Decode<'b> for Address {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let data = d
+            .bytes()?
+            .try_into()
+            .map_err(|_| decode::Error::Message("invalid address length"))?;
+        Ok(Self(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_from_bytes() {
+        assert!(Address::from_bytes(&[1; ADDRESS_SIZE]).is_ok());
+        assert!(Address::from_bytes(&[1; ADDRESS_SIZE - 1]).is_err());
+        assert!(Address::from_bytes(&[1; ADDRESS_SIZE + 1]).is_err());
+    }
+
+    #[test]
+    fn test_encoding() {
+        let addr = Address::from([7; ADDRESS_SIZE]);
+        let mut buf = Vec::new();
+        minicbor::encode(addr, &mut buf).unwrap();
+        assert_eq!(buf[0], 0x55); // bytes(21)
+
+        let decoded: Address = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, addr);
+    }
+}
@@ -0,0 +1,336 @@
+//! A bump allocator for the contracts
+//!
+//! Contract calls are short-lived, so the allocator only moves a pointer forward
+//! in the linear memory and doesn't reuse the freed memory, except the last allocation.
+//! It counts the live allocations and when all of them are freed, the whole arena is reset.
+//! A leaked allocation keeps the count above zero, so with the `runtime` feature the arena
+//! is also reset explicitly at the entry and exit of the exported functions,
+//! whenever no buffer is handed out to the host.
+//!
+//! A reset starts the arena after the memory that is allocated before it. That memory is
+//! not reused and freeing it has no effect, so a leaked allocation that is freed later
+//! can't overlap the new ones. Only the memory that is allocated after the last reset
+//! is given back.
+//!
+//! The allocator keeps the peak of the used memory, which can be reset
+//! at the beginning of a call to measure the memory used by that call.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::Cell;
+use core::ptr::null_mut;
+
+/// The size of a wasm page in bytes
+#[cfg(target_arch = "wasm32")]
+const PAGE_SIZE: usize = 65536;
+
+/// The statistics of the allocator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of bytes that are used in the arena
+    pub used: usize,
+    /// The peak of the used bytes since the last reset of the peak
+    pub peak: usize,
+    /// The number of live allocations
+    pub live: usize,
+}
+
+/// A bump allocator that can be used as `#[global_allocator]`.
+/// It is not thread-safe, and it can only be used as the global allocator in wasm.
+pub struct BumpAllocator {
+    start: Cell<usize>,
+    next: Cell<usize>,
+    end: Cell<usize>,
+    live: Cell<usize>,
+    peak: Cell<usize>,
+    // The lowest address handed out since the last reset
+    floor: Cell<usize>,
+    growable: bool,
+}
+
+// Wasm contracts are single-threaded.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for BumpAllocator {}
+
+impl BumpAllocator {
+    /// creates an allocator that starts from the heap base of the wasm memory
+    /// and grows the memory when it is needed.
+    pub const fn new() -> Self {
+        Self {
+            start: Cell::new(0),
+            next: Cell::new(0),
+            end: Cell::new(0),
+            live: Cell::new(0),
+            peak: Cell::new(0),
+            floor: Cell::new(0),
+            growable: true,
+        }
+    }
+
+    /// creates an allocator on the given region of memory. The region can't grow.
+    ///
+    /// # Safety
+    ///
+    /// The region should be valid for reads and writes and not used by anything else
+    /// while the allocator is alive.
+    pub unsafe fn with_region(start: *mut u8, len: usize) -> Self {
+        let start = start as usize;
+        Self {
+            start: Cell::new(start),
+            next: Cell::new(start),
+            end: Cell::new(start + len),
+            live: Cell::new(0),
+            peak: Cell::new(0),
+            floor: Cell::new(start),
+            growable: false,
+        }
+    }
+
+    /// returns the statistics of the allocator
+    pub fn stats(&self) -> Stats {
+        Stats {
+            used: self.next.get() - self.start.get(),
+            peak: self.peak.get(),
+            live: self.live.get(),
+        }
+    }
+
+    /// sets the peak to the currently used memory
+    pub fn reset_peak(&self) {
+        self.peak.set(self.next.get() - self.start.get());
+    }
+
+    /// forgets the live allocations, so the memory allocated after the reset is given back
+    /// when it is freed. The memory allocated before the reset is kept as it is,
+    /// and freeing it has no effect.
+    pub fn reset(&self) {
+        self.floor.set(self.next.get());
+        self.live.set(0);
+    }
+
+    /// starts the arena from the heap base on the first allocation
+    fn init(&self) {
+        #[cfg(target_arch = "wasm32")]
+        if self.start.get() == 0 {
+            extern "C" {
+                static __heap_base: u8;
+            }
+            let start = core::ptr::addr_of!(__heap_base) as usize;
+            self.start.set(start);
+            self.next.set(start);
+            self.floor.set(start);
+            self.end.set(core::arch::wasm32::memory_size(0) * PAGE_SIZE);
+        }
+    }
+
+    /// grows the region to have at least `end` bytes
+    fn grow(&self, end: usize) -> bool {
+        if !self.growable {
+            return false;
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let pages = (end - self.end.get()).div_ceil(PAGE_SIZE);
+            if core::arch::wasm32::memory_grow(0, pages) == usize::MAX {
+                return false;
+            }
+            self.end.set(self.end.get() + pages * PAGE_SIZE);
+            true
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let _ = end;
+            false
+        }
+    }
+
+    /// moves the next pointer and updates the peak
+    fn bump(&self, next: usize) -> bool {
+        if next > self.end.get() && !self.grow(next) {
+            return false;
+        }
+        self.next.set(next);
+        let used = next - self.start.get();
+        if used > self.peak.get() {
+            self.peak.set(used);
+        }
+        true
+    }
+}
+
+impl Default for BumpAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl GlobalAlloc for BumpAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.init();
+        if self.start.get() == 0 {
+            return null_mut();
+        }
+        let ptr = match self.next.get().checked_add(layout.align() - 1) {
+            Some(n) => n & !(layout.align() - 1),
+            None => return null_mut(),
+        };
+        match ptr.checked_add(layout.size()) {
+            Some(next) if self.bump(next) => {
+                self.live.set(self.live.get() + 1);
+                ptr as *mut u8
+            }
+            _ => null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if (ptr as usize) < self.floor.get() {
+            // It is allocated before the last reset.
+            return;
+        }
+        let live = self.live.get().saturating_sub(1);
+        self.live.set(live);
+        if live == 0 {
+            self.next.set(self.floor.get());
+        } else if ptr as usize + layout.size() == self.next.get() {
+            // The last allocation can be freed.
+            self.next.set(ptr as usize);
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if ptr as usize >= self.floor.get() && ptr as usize + layout.size() == self.next.get() {
+            // The last allocation can be resized in place.
+            return match (ptr as usize).checked_add(new_size) {
+                Some(next) if self.bump(next) => ptr,
+                _ => null_mut(),
+            };
+        }
+        let new_layout = Layout::from_size_align_unchecked(new_size, layout.align());
+        let new_ptr = self.alloc(new_layout);
+        if !new_ptr.is_null() {
+            core::ptr::copy_nonoverlapping(ptr, new_ptr, layout.size().min(new_size));
+            self.dealloc(ptr, layout);
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_allocator(f: impl FnOnce(&BumpAllocator, usize)) {
+        let mut region = [0u64; 32];
+        let alloc = unsafe { BumpAllocator::with_region(region.as_mut_ptr() as *mut u8, 256) };
+        f(&alloc, region.as_ptr() as usize);
+    }
+
+    #[test]
+    fn test_alloc() {
+        with_allocator(|alloc, start| unsafe {
+            let a = alloc.alloc(Layout::from_size_align(3, 1).unwrap());
+            let b = alloc.alloc(Layout::from_size_align(8, 8).unwrap());
+            assert_eq!(a as usize, start);
+            assert_eq!(b as usize, start + 8);
+            assert_eq!(
+                alloc.stats(),
+                Stats {
+                    used: 16,
+                    peak: 16,
+                    live: 2
+                }
+            );
+
+            assert!(alloc
+                .alloc(Layout::from_size_align(256, 1).unwrap())
+                .is_null());
+        });
+    }
+
+    #[test]
+    fn test_reset() {
+        with_allocator(|alloc, start| unsafe {
+            let layout = Layout::from_size_align(16, 1).unwrap();
+            let a = alloc.alloc(layout);
+            let b = alloc.alloc(layout);
+            let c = alloc.alloc(layout);
+
+            // Freeing the last allocation gives the memory back
+            alloc.dealloc(c, layout);
+            assert_eq!(alloc.stats().used, 32);
+
+            // Freeing the others doesn't, until all of them are freed
+            alloc.dealloc(a, layout);
+            assert_eq!(alloc.stats().used, 32);
+            alloc.dealloc(b, layout);
+            assert_eq!(
+                alloc.stats(),
+                Stats {
+                    used: 0,
+                    peak: 48,
+                    live: 0
+                }
+            );
+
+            alloc.reset_peak();
+            assert_eq!(alloc.stats().peak, 0);
+            assert_eq!(alloc.alloc(layout) as usize, start);
+
+            // The memory after a leaked allocation is given back after an explicit reset
+            let _leaked = alloc.alloc(layout);
+            alloc.reset();
+            assert_eq!(
+                alloc.stats(),
+                Stats {
+                    used: 32,
+                    peak: 32,
+                    live: 0
+                }
+            );
+            let d = alloc.alloc(layout);
+            assert_eq!(d as usize, start + 32);
+            alloc.dealloc(d, layout);
+            assert_eq!(alloc.stats().used, 32);
+        });
+    }
+
+    #[test]
+    fn test_free_before_reset() {
+        with_allocator(|alloc, _| unsafe {
+            let layout = Layout::from_size_align(16, 1).unwrap();
+            let a = alloc.alloc(layout);
+            alloc.reset();
+            let b = alloc.alloc(layout);
+
+            // Freeing the allocation made before the reset doesn't free `b`
+            alloc.dealloc(a, layout);
+            assert_eq!(alloc.stats().live, 1);
+            let c = alloc.alloc(layout);
+            assert!(a as usize + 16 <= b as usize);
+            assert!(b as usize + 16 <= c as usize);
+        });
+    }
+
+    #[test]
+    fn test_realloc() {
+        with_allocator(|alloc, _| unsafe {
+            let layout = Layout::from_size_align(16, 1).unwrap();
+            let a = alloc.alloc(layout);
+            *a = 7;
+
+            // The last allocation is resized in place
+            let a = alloc.realloc(a, layout, 32);
+            assert_eq!(alloc.stats().used, 32);
+
+            let b = alloc.alloc(layout);
+            let new_a = alloc.realloc(a, Layout::from_size_align(32, 1).unwrap(), 64);
+            assert_ne!(new_a, a);
+            assert_eq!(*new_a, 7);
+            assert_eq!(alloc.stats().live, 2);
+
+            alloc.dealloc(b, layout);
+            alloc.dealloc(new_a, Layout::from_size_align(64, 1).unwrap());
+            assert_eq!(alloc.stats().used, 0);
+        });
+    }
+}
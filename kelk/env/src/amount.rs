@@ -0,0 +1,118 @@
+//! Amount type for representing the native coins
+//!
+//! The amount is a 128-bit unsigned integer. CBOR has no 128-bit integer,
+//! so the amount is encoded as a byte string of 16 big-endian bytes,
+//! like the host passes it to the contract.
+
+use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
+
+/// The size of the encoded amount in bytes
+pub const AMOUNT_SIZE: usize = 16;
+
+/// The amount of native coins
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Amount(u128);
+
+impl Amount {
+    /// The zero amount
+    pub const ZERO: Self = Self(0);
+    /// The largest amount
+    pub const MAX: Self = Self(u128::MAX);
+
+    /// creates an amount from the number of coins
+    pub const fn new(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// returns the number of coins
+    pub const fn value(&self) -> u128 {
+        self.0
+    }
+
+    /// returns true if the amount is zero
+    pub const fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// creates an amount from its big-endian bytes
+    pub const fn from_be_bytes(bytes: [u8; AMOUNT_SIZE]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// returns the amount as big-endian bytes
+    pub const fn to_be_bytes(&self) -> [u8; AMOUNT_SIZE] {
+        self.0.to_be_bytes()
+    }
+
+    /// adds two amounts, returning `None` if it overflows
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    /// subtracts two amounts, returning `None` if it underflows
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+}
+
+impl From<u128> for Amount {
+    fn from(value: u128) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Amount> for u128 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl Encode for Amount {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.bytes(&self.to_be_bytes())?.ok()
+    }
+}
+
+impl<'b> Decode<'b> for Amount {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let bytes = d
+            .bytes()?
+            .try_into()
+            .map_err(|_| decode::Error::Message("invalid amount length"))?;
+        Ok(Self::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_encoding() {
+        let amount = Amount::new(u128::MAX - 1);
+        let mut buf = Vec::new();
+        minicbor::encode(amount, &mut buf).unwrap();
+        assert_eq!(buf.len(), 1 + AMOUNT_SIZE);
+        assert_eq!(buf[0], 0x50); // bytes(16)
+        assert_eq!(buf[1..], amount.to_be_bytes());
+
+        let decoded: Amount = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, amount);
+
+        // bytes(15)
+        assert!(minicbor::decode::<Amount>(&[0x4f; 16]).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(
+            Amount::new(1).checked_add(Amount::new(2)),
+            Some(Amount::new(3))
+        );
+        assert_eq!(Amount::MAX.checked_add(Amount::new(1)), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::new(1)), None);
+        assert!(Amount::default().is_zero());
+        assert_eq!(u128::from(Amount::from(7)), 7);
+    }
+}
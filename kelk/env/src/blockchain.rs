@@ -1,6 +1,37 @@
 //! The Blockchain APIs for interacting with blockchain
+//!
+//! ## Calling other contracts
+//!
+//! A contract can query another contract or call its process entry.
+//! The callee's result is returned in the same [`Envelope`] that `do_execute` produces,
+//! so the callee's `Result` can be decoded on the caller side.
+//!
+//! The host enforces these rules on nested calls:
+//!
+//! - The depth of nested calls can't exceed [`MAX_CALL_DEPTH`],
+//!   otherwise the call fails with [`ERR_CALL_DEPTH_EXCEEDED`](crate::error::ERR_CALL_DEPTH_EXCEEDED).
+//! - A contract can't be processed while it is already in the call stack,
+//!   otherwise the call fails with [`ERR_REENTRANCY`](crate::error::ERR_REENTRANCY). Queries can re-enter.
+//! - Queries are read-only. A process call made while executing a query
+//!   fails with [`ERR_READ_ONLY`](crate::error::ERR_READ_ONLY).
+//! - The sub-messages of a response are sent after the call returns and leaves the call stack,
+//!   so a sub-message can call back into the contract that sent it.
+//!   If a sub-message fails, the call is reverted and it fails with
+//!   [`ERR_SUB_MESSAGE_FAILED`](crate::error::ERR_SUB_MESSAGE_FAILED), since the result of
+//!   the sub-message is not in the format of the caller's result.
 
+use crate::address::Address;
+use crate::envelope::Envelope;
+use crate::error::Error;
 use crate::params::*;
+use crate::response::Response;
+use alloc::vec::Vec;
+use minicbor::{Decode, Encode};
+
+/// The maximum depth of nested contract calls.
+pub const MAX_CALL_DEPTH: u32 = 8;
+
+pub use crate::amount::Amount;
 
 /// `ContextAPI` provides the storage and blockchain APIs.
 /// It can't be copied or cloned since it doesn't have Copy and Clone traits.
@@ -8,4 +39,192 @@ pub trait Blockchain {
     /// TODO move it to lib crate
     /// gets the parameter value
     fn get_param(&self, param_id: i32) -> Option<ParamType>;
+
+    /// queries the contract at the given address with the CBOR encoded message.
+    /// It returns the CBOR encoded envelope of the callee.
+    fn query_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// calls the process entry of the contract at the given address with the CBOR encoded message.
+    /// It returns the CBOR encoded envelope of the callee.
+    fn process_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error>;
+
+    /// returns the amount of coins that is attached to the current call.
+    /// The attached amount is already added to the contract's balance.
+    fn attached_amount(&self) -> Result<Amount, Error>;
+
+    /// returns the balance of the contract.
+    fn balance(&self) -> Result<Amount, Error>;
+
+    /// transfers the given amount of coins from the contract's balance to the given address.
+    fn transfer(&self, to: &Address, amount: Amount) -> Result<(), Error>;
+
+    /// returns the amount of gas left for the current call.
+    /// Contracts can check it to stop long-running loops gracefully.
+    fn gas_remaining(&self) -> u64;
+
+    /// consumes the given amount of gas.
+    /// It fails with [`ERR_OUT_OF_GAS`](crate::error::ERR_OUT_OF_GAS) if there is not enough gas left.
+    fn consume_gas(&self, amount: u64) -> Result<(), Error>;
+
+    /// returns the number of the block that contains the current call.
+    fn block_number(&self) -> u64;
+}
+
+impl<'a> dyn Blockchain + 'a {
+    /// queries the contract at the given address.
+    /// The outer result fails if the host can't execute the call,
+    /// the inner result is the result of the callee.
+    pub fn query<M, R, E>(&self, addr: &Address, msg: &M) -> Result<Result<R, E>, Error>
+    where
+        M: Encode,
+        R: for<'b> Decode<'b>,
+        E: for<'b> Decode<'b>,
+    {
+        let data = self.query_contract(addr, &encode_msg(msg)?)?;
+        decode_envelope(&data)
+    }
+
+    /// calls the process entry of the contract at the given address.
+    /// The outer result fails if the host can't execute the call,
+    /// the inner result is the result of the callee.
+    pub fn process<M, E>(&self, addr: &Address, msg: &M) -> Result<Result<Response, E>, Error>
+    where
+        M: Encode,
+        E: for<'b> Decode<'b>,
+    {
+        let data = self.process_contract(addr, &encode_msg(msg)?)?;
+        decode_envelope(&data)
+    }
+}
+
+pub(crate) fn encode_msg<M: Encode>(msg: &M) -> Result<Vec<u8>, Error> {
+    let mut buf = Vec::new();
+    minicbor::encode(msg, &mut buf).map_err(|_| Error::GenericError("encoding failed"))?;
+    Ok(buf)
+}
+
+pub(crate) fn decode_envelope<R, E>(data: &[u8]) -> Result<Result<R, E>, Error>
+where
+    R: for<'b> Decode<'b>,
+    E: for<'b> Decode<'b>,
+{
+    let envelope: Envelope<R, E> =
+        minicbor::decode(data).map_err(|_| Error::GenericError("invalid envelope"))?;
+    match envelope {
+        Envelope::Ok(res) => Ok(Ok(res)),
+        Envelope::Err(err) => Ok(Err(err)),
+        Envelope::InvalidMessage(reason) => Err(Error::InvalidMessage(reason)),
+        Envelope::AlreadyInstantiated => Err(Error::GenericError("already instantiated")),
+        Envelope::UnsupportedLayout(_) => Err(Error::GenericError("unsupported storage layout")),
+        Envelope::NotInstantiated => Err(Error::GenericError("not instantiated")),
+        Envelope::StorageError(reason) => Err(Error::StorageError(reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ERR_CONTRACT_NOT_FOUND, ERR_INSUFFICIENT_FUNDS};
+    use crate::mock::mock_context;
+
+    #[test]
+    fn test_query_contract() {
+        let addr = Address::from([1; 21]);
+        let mut ctx = mock_context(0);
+        ctx.blockchain
+            .mock_query(addr, |a: i32| -> Result<i32, i32> {
+                match a {
+                    0 => Err(0x0e),
+                    _ => Ok(a * 2),
+                }
+            });
+
+        let ctx = ctx.as_ref();
+        let res: Result<i32, i32> = ctx.blockchain.query(&addr, &2).unwrap();
+        assert_eq!(res, Ok(4));
+
+        let res: Result<i32, i32> = ctx.blockchain.query(&addr, &0).unwrap();
+        assert_eq!(res, Err(0x0e));
+
+        let res: Result<Result<i32, i32>, Error> = ctx.blockchain.query(&addr, &"foo");
+        assert!(matches!(res, Err(Error::InvalidMessage(reason)) if !reason.is_empty()));
+    }
+
+    #[test]
+    fn test_process_contract() {
+        let addr = Address::from([1; 21]);
+        let mut ctx = mock_context(0);
+        ctx.blockchain
+            .mock_process(addr, |a: i32| -> Result<Response, i32> {
+                Ok(Response::new().add_attribute("a", "1").set_data([a as u8]))
+            });
+
+        let ctx = ctx.as_ref();
+        let res: Result<Response, i32> = ctx.blockchain.process(&addr, &7).unwrap();
+        assert_eq!(res.unwrap().data, Some([7].into()));
+    }
+
+    #[test]
+    fn test_contract_not_found() {
+        let ctx = mock_context(0);
+        let ctx = ctx.as_ref();
+        let addr = Address::from([2; 21]);
+
+        let res: Result<Result<i32, i32>, Error> = ctx.blockchain.query(&addr, &1);
+        assert!(matches!(res, Err(Error::HostError(ERR_CONTRACT_NOT_FOUND))));
+
+        let res: Result<Result<Response, i32>, Error> = ctx.blockchain.process(&addr, &1);
+        assert!(matches!(res, Err(Error::HostError(ERR_CONTRACT_NOT_FOUND))));
+    }
+
+    #[test]
+    fn test_transfer() {
+        let alice = Address::from([1; 21]);
+        let mut ctx = mock_context(0);
+        ctx.blockchain
+            .set_balance(*ctx.blockchain.address(), Amount::new(100));
+        ctx.blockchain.attach(Amount::new(50)).unwrap();
+
+        let ctx_ref = ctx.as_ref();
+        assert_eq!(
+            ctx_ref.blockchain.attached_amount().unwrap(),
+            Amount::new(50)
+        );
+        assert_eq!(ctx_ref.blockchain.balance().unwrap(), Amount::new(150));
+
+        ctx_ref
+            .blockchain
+            .transfer(&alice, Amount::new(120))
+            .unwrap();
+        assert_eq!(ctx_ref.blockchain.balance().unwrap(), Amount::new(30));
+        assert_eq!(ctx.blockchain.balance_of(&alice), Amount::new(120));
+
+        assert!(matches!(
+            ctx_ref.blockchain.transfer(&alice, Amount::new(31)),
+            Err(Error::HostError(ERR_INSUFFICIENT_FUNDS))
+        ));
+        assert_eq!(ctx_ref.blockchain.balance().unwrap(), Amount::new(30));
+
+        ctx_ref
+            .blockchain
+            .transfer(ctx.blockchain.address(), Amount::new(30))
+            .unwrap();
+        assert_eq!(ctx_ref.blockchain.balance().unwrap(), Amount::new(30));
+    }
+
+    #[test]
+    fn test_transfer_overflow() {
+        let alice = Address::from([1; 21]);
+        let mut ctx = mock_context(0);
+        ctx.blockchain.set_balance(alice, Amount::MAX);
+        ctx.blockchain
+            .set_balance(*ctx.blockchain.address(), Amount::MAX);
+        assert!(ctx.blockchain.attach(Amount::new(1)).is_err());
+        assert_eq!(ctx.blockchain.attached_amount().unwrap(), Amount::ZERO);
+
+        let ctx_ref = ctx.as_ref();
+        assert!(ctx_ref.blockchain.transfer(&alice, Amount::new(1)).is_err());
+        assert_eq!(ctx_ref.blockchain.balance().unwrap(), Amount::MAX);
+        assert_eq!(ctx.blockchain.balance_of(&alice), Amount::MAX);
+    }
 }
@@ -1,6 +1,6 @@
 //! The context for running contract actor
 
-use crate::{blockchain::Blockchain, storage::Storage};
+use crate::{blockchain::Blockchain, crypto::Crypto, storage::Storage};
 
 /// `Context` owns the `ContextAPI` reference.
 pub struct Context<'a> {
@@ -9,6 +9,9 @@ pub struct Context<'a> {
 
     /// The instance of storage APIs
     pub storage: &'a Storage,
+
+    /// The instance of cryptographic APIs
+    pub crypto: &'a Crypto,
 }
 
 /// `OwnedContext` owns the `ContextAPI` instance. It allow dependency injection at runtime.
@@ -19,6 +22,8 @@ pub struct OwnedContext<B: Blockchain> {
     pub blockchain: B,
     /// The instance of mocked Storage
     pub storage: Storage,
+    /// The instance of mocked Crypto
+    pub crypto: Crypto,
 }
 
 impl<B: Blockchain> OwnedContext<B> {
@@ -27,6 +32,7 @@ impl<B: Blockchain> OwnedContext<B> {
         Context {
             blockchain: &self.blockchain,
             storage: &self.storage,
+            crypto: &self.crypto,
         }
     }
 }
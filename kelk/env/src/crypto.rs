@@ -0,0 +1,182 @@
+//! Cryptographic APIs for hashing and verifying signatures
+
+use crate::error::Error;
+use alloc::boxed::Box;
+
+/// The size of the Ed25519 public key in bytes
+pub const ED25519_PUBLIC_KEY_SIZE: usize = 32;
+/// The size of the Ed25519 signature in bytes
+pub const ED25519_SIGNATURE_SIZE: usize = 64;
+/// The size of the compressed BLS public key (G2 point) in bytes
+pub const BLS_PUBLIC_KEY_SIZE: usize = 96;
+/// The size of the compressed BLS signature (G1 point) in bytes
+pub const BLS_SIGNATURE_SIZE: usize = 48;
+/// The domain separation tag for hashing messages to G1 in BLS signatures
+pub const BLS_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// A 32-byte hash
+pub type Hash = [u8; 32];
+
+/// the cryptographic APIs that provided by the host
+pub trait CryptoAPI {
+    /// calculates the Blake2b hash of `data` with 256-bit output
+    fn blake2b_256(&self, data: &[u8]) -> Result<Hash, Error>;
+
+    /// calculates the SHA-256 hash of `data`
+    fn sha256(&self, data: &[u8]) -> Result<Hash, Error>;
+
+    /// verifies the Ed25519 `signature` of `msg` against the `public_key`
+    fn verify_ed25519(
+        &self,
+        public_key: &[u8; ED25519_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; ED25519_SIGNATURE_SIZE],
+    ) -> Result<bool, Error>;
+
+    /// verifies the BLS `signature` of `msg` against the `public_key`.
+    /// Signatures are in G1 and public keys are in G2, both compressed.
+    fn verify_bls(
+        &self,
+        public_key: &[u8; BLS_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; BLS_SIGNATURE_SIZE],
+    ) -> Result<bool, Error>;
+}
+
+/// Crypto object
+pub struct Crypto {
+    /// APIs the provided by th host
+    pub api: Box<dyn CryptoAPI>,
+}
+
+impl Crypto {
+    /// creates a new instance of crypto
+    pub fn new(api: Box<dyn CryptoAPI>) -> Self {
+        Self { api }
+    }
+
+    /// calculates the Blake2b hash of `data` with 256-bit output
+    pub fn blake2b_256(&self, data: &[u8]) -> Result<Hash, Error> {
+        self.api.blake2b_256(data)
+    }
+
+    /// calculates the SHA-256 hash of `data`
+    pub fn sha256(&self, data: &[u8]) -> Result<Hash, Error> {
+        self.api.sha256(data)
+    }
+
+    /// verifies the Ed25519 `signature` of `msg` against the `public_key`.
+    /// It returns an error if the key or the signature doesn't have the correct size.
+    pub fn verify_ed25519(
+        &self,
+        public_key: &[u8],
+        msg: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, Error> {
+        let public_key = public_key
+            .try_into()
+            .map_err(|_| Error::GenericError("invalid public key length"))?;
+        let signature = signature
+            .try_into()
+            .map_err(|_| Error::GenericError("invalid signature length"))?;
+        self.api.verify_ed25519(public_key, msg, signature)
+    }
+
+    /// verifies the BLS `signature` of `msg` against the `public_key`.
+    /// It returns an error if the key or the signature doesn't have the correct size.
+    pub fn verify_bls(
+        &self,
+        public_key: &[u8],
+        msg: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, Error> {
+        let public_key = public_key
+            .try_into()
+            .map_err(|_| Error::GenericError("invalid public key length"))?;
+        let signature = signature
+            .try_into()
+            .map_err(|_| Error::GenericError("invalid signature length"))?;
+        self.api.verify_bls(public_key, msg, signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock::mock_context;
+
+    #[test]
+    fn test_hash() {
+        let ctx = mock_context(0);
+        let ctx = ctx.as_ref();
+
+        // https://www.rfc-editor.org/rfc/rfc7693#appendix-A
+        assert_eq!(
+            ctx.crypto.blake2b_256(b"abc").unwrap(),
+            [
+                0xbd, 0xdd, 0x81, 0x3c, 0x63, 0x42, 0x39, 0x72, 0x31, 0x71, 0xef, 0x3f, 0xee, 0x98,
+                0x57, 0x9b, 0x94, 0x96, 0x4e, 0x3b, 0xb1, 0xcb, 0x3e, 0x42, 0x72, 0x62, 0xc8, 0xc0,
+                0x68, 0xd5, 0x23, 0x19
+            ]
+        );
+        assert_eq!(
+            ctx.crypto.sha256(b"abc").unwrap(),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_ed25519() {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let ctx = mock_context(0);
+        let ctx = ctx.as_ref();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = key.verifying_key().to_bytes();
+        let signature = key.sign(b"foo").to_bytes();
+
+        assert!(ctx
+            .crypto
+            .verify_ed25519(&public_key, b"foo", &signature)
+            .unwrap());
+        assert!(!ctx
+            .crypto
+            .verify_ed25519(&public_key, b"bar", &signature)
+            .unwrap());
+        assert!(ctx
+            .crypto
+            .verify_ed25519(&public_key[1..], b"foo", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn test_verify_bls() {
+        use crate::mock::ExpandMsgSha256;
+        use bls12_381::hash_to_curve::HashToCurve;
+        use bls12_381::{G1Affine, G1Projective, G2Affine, Scalar};
+
+        let ctx = mock_context(0);
+        let ctx = ctx.as_ref();
+        let secret = Scalar::from(0x1234_5678);
+        let public_key = G2Affine::from(G2Affine::generator() * secret).to_compressed();
+        let hash =
+            <G1Projective as HashToCurve<ExpandMsgSha256>>::hash_to_curve(b"foo", super::BLS_DST);
+        let signature = G1Affine::from(hash * secret).to_compressed();
+
+        assert!(ctx
+            .crypto
+            .verify_bls(&public_key, b"foo", &signature)
+            .unwrap());
+        assert!(!ctx
+            .crypto
+            .verify_bls(&public_key, b"bar", &signature)
+            .unwrap());
+        assert!(!ctx
+            .crypto
+            .verify_bls(&public_key, b"foo", &[0; 48])
+            .unwrap());
+    }
+}
@@ -0,0 +1,515 @@
+//! The entry points of the contracts, independent of the wasm memory
+//!
+//! The functions decode the CBOR message, call the contract function with the given
+//! context and return the CBOR encoded [`Envelope`]. The wasm exports (`do_process`, ...)
+//! read the message from the wasm memory, call them with the host context and
+//! pass the result back to the host. Native tests can call them with a mocked context
+//! and raw CBOR bytes and get the same results as the wasm build.
+//!
+//! If the message can't be decoded, the contract function is not called
+//! and `Envelope::InvalidMessage` is returned instead.
+//!
+//! Instantiate and migrate write the contract metadata into the storage
+//! after a successful call. An instantiate message sent to an instantiated contract
+//! is refused with `Envelope::AlreadyInstantiated`.
+//! The migrate function receives the contract version that was written before.
+//!
+//! The storage of a new contract is all zeros and it has no layout header yet.
+//! The header is written by instantiate or by the first process call, and queries read
+//! the blank storage as the current layout, so the contracts without an instantiate
+//! function work on a new storage too.
+//!
+//! The contracts that are deployed before the reserved area have the legacy storage layout.
+//! Instantiate, process and query messages sent to them are refused with
+//! `Envelope::UnsupportedLayout` until they are migrated. Their migrate function receives no version and it should move
+//! the data by [`Storage::migrate_layout`](crate::storage::Storage::migrate_layout).
+//! A migrate message sent to a contract with the current layout that is not instantiated
+//! is refused with `Envelope::NotInstantiated`.
+//!
+//! If Kelk fails to read or write its records in the storage, `Envelope::StorageError`
+//! is returned with the reason.
+
+use crate::context::Context;
+use crate::envelope::Envelope;
+use crate::error::Error;
+use crate::metadata::{
+    is_instantiated, read_metadata, write_metadata, ContractVersion, Metadata, KELK_VERSION,
+};
+use crate::response::Response;
+use crate::storage::{Storage, LAYOUT_VERSION, LEGACY_LAYOUT_VERSION};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use minicbor::{Decode, Encode};
+
+/// executes the instantiate function.
+/// The function can return a [`Response`] or anything that converts into it, like `()`.
+/// On success, the layout header and the contract metadata are written
+/// with the given `name` and `version`.
+/// If the contract is already instantiated, the function is not called
+/// and `Envelope::AlreadyInstantiated` is returned.
+/// A legacy storage that is not blank is refused with `Envelope::UnsupportedLayout`,
+/// because it has the data of a deployed contract. It should be migrated first.
+pub fn instantiate<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    instantiate_fn: &dyn Fn(Context, D) -> Result<R, E>,
+    name: &str,
+    version: &str,
+    ctx: Context,
+    msg: &'a [u8],
+) -> Vec<u8> {
+    execute(
+        &|ctx, msg| {
+            let storage = ctx.storage;
+            let layout = layout_version(storage, false)?;
+            if layout != LAYOUT_VERSION {
+                return Ok(Envelope::UnsupportedLayout(layout));
+            }
+            if is_instantiated(storage)? {
+                return Ok(Envelope::AlreadyInstantiated);
+            }
+            let block = ctx.blockchain.block_number();
+            let res = instantiate_fn(ctx, msg).map(Into::into);
+            if res.is_ok() {
+                if storage.layout_version()? != LAYOUT_VERSION {
+                    storage.write_layout_header()?;
+                }
+                let metadata = Metadata::new(ContractVersion::new(name, version), block);
+                write_metadata(storage, &metadata)?;
+            }
+            Ok(Envelope::from(res))
+        },
+        ctx,
+        msg,
+    )
+}
+
+/// executes the migrate function.
+/// The function receives the contract version written by the previous code,
+/// or `None` if the contract has the legacy storage layout.
+/// The function should migrate the legacy layout, otherwise `Envelope::UnsupportedLayout`
+/// is returned.
+/// If the contract has the current layout but it is not instantiated, the function is not
+/// called and `Envelope::NotInstantiated` is returned.
+/// On success, the contract metadata is updated with the given `name` and `version`.
+#[allow(clippy::type_complexity)]
+pub fn migrate<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    migrate_fn: &dyn Fn(Context, Option<ContractVersion>, D) -> Result<R, E>,
+    name: &str,
+    version: &str,
+    ctx: Context,
+    msg: &'a [u8],
+) -> Vec<u8> {
+    execute(
+        &|ctx, msg| {
+            let storage = ctx.storage;
+            let layout = storage.layout_version()?;
+            let old = match layout {
+                LAYOUT_VERSION => match read_metadata(storage)? {
+                    Some(old) if old.initialized => Some(old),
+                    _ => return Ok(Envelope::NotInstantiated),
+                },
+                LEGACY_LAYOUT_VERSION => None,
+                _ => return Ok(Envelope::UnsupportedLayout(layout)),
+            };
+            let block = ctx.blockchain.block_number();
+            let old_version = old.as_ref().map(|m| m.contract.clone());
+            let res = migrate_fn(ctx, old_version, msg).map(Into::into);
+            if res.is_ok() {
+                let layout = storage.layout_version()?;
+                if layout != LAYOUT_VERSION {
+                    return Ok(Envelope::UnsupportedLayout(layout));
+                }
+                let contract = ContractVersion::new(name, version);
+                let metadata = match old {
+                    Some(old) => Metadata {
+                        kelk_version: KELK_VERSION.into(),
+                        contract,
+                        ..old
+                    },
+                    // The legacy contract was instantiated before the metadata existed.
+                    None => Metadata::new(contract, block),
+                };
+                write_metadata(storage, &metadata)?;
+            }
+            Ok(Envelope::from(res))
+        },
+        ctx,
+        msg,
+    )
+}
+
+/// executes the process function.
+/// The function can return a [`Response`] or anything that converts into it, like `()`.
+/// The layout header is written into a blank storage before calling the function.
+/// If the storage doesn't have the current layout, the function is not called
+/// and `Envelope::UnsupportedLayout` is returned.
+pub fn process<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    process_fn: &dyn Fn(Context, D) -> Result<R, E>,
+    ctx: Context,
+    msg: &'a [u8],
+) -> Vec<u8> {
+    execute(
+        &|ctx, msg| match layout_version(ctx.storage, true)? {
+            LAYOUT_VERSION => Ok(Envelope::from(process_fn(ctx, msg).map(Into::into))),
+            layout => Ok(Envelope::UnsupportedLayout(layout)),
+        },
+        ctx,
+        msg,
+    )
+}
+
+/// executes the query function.
+/// A blank storage is read as the current layout.
+/// If the storage doesn't have the current layout, the function is not called
+/// and `Envelope::UnsupportedLayout` is returned.
+pub fn query<'a, D: Decode<'a>, R: Encode, E: Encode>(
+    query_fn: &dyn Fn(Context, D) -> Result<R, E>,
+    ctx: Context,
+    msg: &'a [u8],
+) -> Vec<u8> {
+    execute(
+        &|ctx, msg| match layout_version(ctx.storage, false)? {
+            LAYOUT_VERSION => Ok(Envelope::from(query_fn(ctx, msg))),
+            layout => Ok(Envelope::UnsupportedLayout(layout)),
+        },
+        ctx,
+        msg,
+    )
+}
+
+/// returns the layout version of the storage, taking a blank storage as the current layout.
+/// If `init` is true, the layout header is written into the blank storage.
+fn layout_version(storage: &Storage, init: bool) -> Result<u8, Error> {
+    if !storage.is_blank()? {
+        return storage.layout_version();
+    }
+    if init {
+        storage.write_layout_header()?;
+    }
+    Ok(LAYOUT_VERSION)
+}
+
+/// returns the encoded `Envelope::InvalidMessage` with the given reason
+pub(crate) fn invalid_message(reason: &str) -> Vec<u8> {
+    encode(Envelope::<(), ()>::InvalidMessage(reason.to_string()))
+}
+
+/// decodes the message and executes the function.
+/// The errors of reading and writing the Kelk records are returned as `Envelope::StorageError`.
+#[allow(clippy::type_complexity)]
+fn execute<'a, D: Decode<'a>, R: Encode, E: Encode>(
+    func: &dyn Fn(Context, D) -> Result<Envelope<R, E>, Error>,
+    ctx: Context,
+    msg: &'a [u8],
+) -> Vec<u8> {
+    match minicbor::decode(msg) {
+        Ok(msg) => {
+            encode(func(ctx, msg).unwrap_or_else(|err| Envelope::StorageError(err.to_string())))
+        }
+        Err(err) => invalid_message(&err.to_string()),
+    }
+}
+
+fn encode<R: Encode, E: Encode>(res: Envelope<R, E>) -> Vec<u8> {
+    let mut vec = Vec::new();
+    minicbor::encode(res, &mut vec).expect("Encoding failed");
+    vec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{mock_context, MockStorageAPI};
+    use crate::storage::{StorageAPI, RESERVED_SIZE};
+    use alloc::boxed::Box;
+    use alloc::vec;
+
+    #[test]
+    fn test_instantiate() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = instantiate(
+            &|_: Context, _: i32| -> Result<(), i32> { Ok(()) },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]); // Result::Ok(Response::default()) -> http://cbor.me/?bytes=820084f6808080
+
+        let metadata = read_metadata(&ctx.storage).unwrap().unwrap();
+        assert_eq!(metadata.contract, ContractVersion::new("foo", "0.1.0"));
+        assert_eq!(metadata.block, 1);
+
+        let res = instantiate(
+            &|_: Context, _: i32| -> Result<(), i32> { panic!("should not be called") },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x03, 0xf6]); // AlreadyInstantiated -> http://cbor.me/?bytes=8203f6
+    }
+
+    #[test]
+    fn test_instantiate_error() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = instantiate(
+            &|_: Context, _: i32| -> Result<(), i32> { Err(0x0e) },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+        assert!(!is_instantiated(&ctx.storage).unwrap());
+    }
+
+    #[test]
+    fn test_migrate() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+        let migrate_fn = |_: Context, old: Option<ContractVersion>, _: i32| -> Result<(), i32> {
+            match old {
+                Some(old) if old.version == "0.1.0" => Ok(()),
+                _ => Err(0x0e),
+            }
+        };
+
+        let res = migrate(&migrate_fn, "foo", "0.1.0", ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x05, 0xf6]); // NotInstantiated -> http://cbor.me/?bytes=8205f6
+        assert_eq!(read_metadata(&ctx.storage).unwrap(), None);
+
+        let res = instantiate(
+            &|_: Context, _: i32| -> Result<(), i32> { Ok(()) },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]); // Result::Ok(Response::default()) -> http://cbor.me/?bytes=820084f6808080
+
+        let res = migrate(&migrate_fn, "foo", "0.2.0", ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]);
+        let metadata = read_metadata(&ctx.storage).unwrap().unwrap();
+        assert_eq!(metadata.contract, ContractVersion::new("foo", "0.2.0"));
+
+        let res = migrate(&migrate_fn, "foo", "0.3.0", ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+    }
+
+    #[test]
+    fn test_storage_error() {
+        let mut ctx = mock_context(64);
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 64).with_layout();
+        let faults = api.faults();
+        ctx.storage = Storage::new(Box::new(api));
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+        let instantiate_fn = |_: Context, _: i32| -> Result<(), i32> { Ok(()) };
+
+        faults.fail_reads(0..RESERVED_SIZE, 0x0e);
+        let res = instantiate(&instantiate_fn, "foo", "0.1.0", ctx.as_ref(), &msg);
+        let res: Envelope<(), i32> = minicbor::decode(&res).unwrap();
+        assert_eq!(
+            res,
+            Envelope::StorageError(Error::HostError(0x0e).to_string())
+        );
+
+        faults.clear();
+        faults.fail_writes(0..RESERVED_SIZE, 0x0e);
+        let res = instantiate(&instantiate_fn, "foo", "0.1.0", ctx.as_ref(), &msg);
+        let res: Envelope<(), i32> = minicbor::decode(&res).unwrap();
+        assert!(matches!(res, Envelope::StorageError(_)));
+        assert!(!is_instantiated(&ctx.storage).unwrap());
+    }
+
+    #[test]
+    fn test_legacy_layout() {
+        let mut ctx = mock_context(64);
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 64);
+        api.write(0, &7_u32.to_be_bytes()).unwrap();
+        ctx.storage = Storage::new(Box::new(api));
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let process_fn = |ctx: Context, _: i32| -> Result<u32, i32> {
+            ctx.storage.read_u32(0).map_err(|_| 0x0e)
+        };
+        let res = query(&process_fn, ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x04, 0x00]); // UnsupportedLayout(0) -> http://cbor.me/?bytes=820400
+
+        let res = instantiate(
+            &|_: Context, _: i32| -> Result<(), i32> { panic!("should not be called") },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x04, 0x00]);
+        assert_eq!(ctx.storage.layout_version().unwrap(), LEGACY_LAYOUT_VERSION);
+        assert_eq!(
+            ctx.storage.read_reserved(0, 4).unwrap(),
+            7_u32.to_be_bytes()
+        );
+
+        let res = migrate(
+            &|_: Context, _: Option<ContractVersion>, _: i32| -> Result<(), i32> { Ok(()) },
+            "foo",
+            "0.2.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x04, 0x00]);
+
+        let res = migrate(
+            &|ctx: Context, old: Option<ContractVersion>, _: i32| -> Result<(), i32> {
+                assert_eq!(old, None);
+                ctx.storage.migrate_layout(4).map_err(|_| 0x0e)
+            },
+            "foo",
+            "0.2.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]);
+        let metadata = read_metadata(&ctx.storage).unwrap().unwrap();
+        assert_eq!(metadata.contract, ContractVersion::new("foo", "0.2.0"));
+
+        let res = query(&process_fn, ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x00, 0x07]); // Result::Ok(7) -> http://cbor.me/?bytes=820007
+    }
+
+    #[test]
+    fn test_blank_storage() {
+        let mut ctx = mock_context(64);
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 64);
+        ctx.storage = Storage::new(Box::new(api));
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let query_fn = |ctx: Context, _: i32| -> Result<u32, i32> {
+            ctx.storage.read_u32(0).map_err(|_| 0x0e)
+        };
+        let res = query(&query_fn, ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x00, 0x00]); // Result::Ok(0) -> http://cbor.me/?bytes=820000
+        assert!(ctx.storage.is_blank().unwrap());
+
+        let res = process(
+            &|ctx: Context, _: i32| -> Result<(), i32> {
+                ctx.storage.write_u32(0, 7).map_err(|_| 0x0e)
+            },
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]); // Result::Ok(Response::default()) -> http://cbor.me/?bytes=820084f6808080
+        assert_eq!(ctx.storage.layout_version().unwrap(), LAYOUT_VERSION);
+        assert_eq!(read_metadata(&ctx.storage).unwrap(), None);
+
+        let res = query(&query_fn, ctx.as_ref(), &msg);
+        assert_eq!(res, vec![0x82, 0x00, 0x07]); // Result::Ok(7) -> http://cbor.me/?bytes=820007
+
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 64);
+        ctx.storage = Storage::new(Box::new(api));
+        let res = instantiate(
+            &|ctx: Context, _: i32| -> Result<(), i32> {
+                ctx.storage.write_u32(0, 7).map_err(|_| 0x0e)
+            },
+            "foo",
+            "0.1.0",
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]);
+        assert_eq!(ctx.storage.layout_version().unwrap(), LAYOUT_VERSION);
+        assert!(is_instantiated(&ctx.storage).unwrap());
+    }
+
+    #[test]
+    fn test_process() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = process(
+            &|ctx: Context, _: i32| -> Result<(), i32> {
+                ctx.storage.write_u32(0, 0x0e).unwrap();
+                Ok(())
+            },
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]); // Result::Ok(Response::default()) -> http://cbor.me/?bytes=820084f6808080
+        assert_eq!(ctx.storage.read_u32(0).unwrap(), 0x0e);
+    }
+
+    #[test]
+    fn test_process_error() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = process(
+            &|_: Context, _: i32| -> Result<(), i32> { Err(0x0e) },
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+    }
+
+    #[test]
+    fn test_process_response() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = process(
+            &|_: Context, _: i32| -> Result<Response, i32> {
+                Ok(Response::new().add_attribute("foo", "bar"))
+            },
+            ctx.as_ref(),
+            &msg,
+        );
+        let res: Envelope<Response, i32> = minicbor::decode(&res).unwrap();
+        assert_eq!(
+            res,
+            Envelope::Ok(Response::new().add_attribute("foo", "bar"))
+        );
+    }
+
+    #[test]
+    fn test_query() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = query(
+            &|_: Context, _: i32| -> Result<&str, i32> { Ok("foo") },
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x00, 0x63, 0x66, 0x6f, 0x6f]); // Result::Ok("foo") -> http://cbor.me/?bytes=0x820063666f6f
+    }
+
+    #[test]
+    fn test_query_error() {
+        let ctx = mock_context(64);
+        let msg = vec![0x00]; // http://cbor.me/?bytes=8100
+
+        let res = query(
+            &|_: Context, _: i32| -> Result<&str, i32> { Err(0x0e) },
+            ctx.as_ref(),
+            &msg,
+        );
+        assert_eq!(res, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+    }
+
+    #[test]
+    fn test_invalid_message() {
+        let ctx = mock_context(64);
+        let msg = vec![0x61, 0x61]; // "a" -> http://cbor.me/?bytes=6161
+
+        let res = process(
+            &|_: Context, _: i32| -> Result<(), i32> { panic!("should not be called") },
+            ctx.as_ref(),
+            &msg,
+        );
+        let res: Envelope<(), i32> = minicbor::decode(&res).unwrap();
+        assert!(matches!(res, Envelope::InvalidMessage(_)));
+    }
+}
@@ -0,0 +1,178 @@
+//! The envelope that wraps the result of executing a contract's entry point.
+//!
+//! The `Ok` and `Err` variants are encoded exactly like a `Result`: a two-element array
+//! where the first element is the variant tag. Other tags are reserved by Kelk
+//! to report failures that happen outside of the contract's own logic.
+
+use alloc::string::String;
+use minicbor::{decode, encode, Decode, Decoder, Encode, Encoder};
+
+/// Tag of the message that executed successfully.
+pub const TAG_OK: u32 = 0;
+/// Tag of the message that is rejected by the contract.
+pub const TAG_ERR: u32 = 1;
+/// Tag of the message that couldn't be decoded.
+pub const TAG_INVALID_MESSAGE: u32 = 2;
+/// Tag of the instantiate message that is sent to an already instantiated contract.
+pub const TAG_ALREADY_INSTANTIATED: u32 = 3;
+/// Tag of the message that is refused because the storage layout is not supported.
+pub const TAG_UNSUPPORTED_LAYOUT: u32 = 4;
+/// Tag of the migrate message that is sent to a contract that is not instantiated.
+pub const TAG_NOT_INSTANTIATED: u32 = 5;
+/// Tag of the message that failed because Kelk couldn't read or write its records in the storage.
+pub const TAG_STORAGE_ERROR: u32 = 6;
+
+/// `Envelope` is what the host receives after executing the contract's entry point.
+/// The host and the clients can tell "bad input" from "contract rejected" by checking the variant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Envelope<R, E> {
+    /// The contract executed the message successfully.
+    Ok(R),
+    /// The contract rejected the message with its own error.
+    Err(E),
+    /// The message couldn't be decoded, therefore the contract didn't execute it.
+    /// It contains the decoding error.
+    InvalidMessage(String),
+    /// The contract is already instantiated, therefore the instantiate message is refused.
+    AlreadyInstantiated,
+    /// The storage doesn't have the layout that the contract's code expects,
+    /// therefore the message is refused. It contains the layout version of the storage.
+    /// A contract with the legacy layout should be migrated first.
+    UnsupportedLayout(u8),
+    /// The contract is not instantiated, therefore the migrate message is refused.
+    NotInstantiated,
+    /// Kelk couldn't read or write its records in the storage, like the contract metadata.
+    /// It contains the storage error.
+    StorageError(String),
+}
+
+impl<R, E> From<Result<R, E>> for Envelope<R, E> {
+    fn from(res: Result<R, E>) -> Self {
+        match res {
+            Ok(v) => Envelope::Ok(v),
+            Err(e) => Envelope::Err(e),
+        }
+    }
+}
+
+impl<R: Encode, E: Encode> Encode for Envelope<R, E> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?;
+        match self {
+            Envelope::Ok(v) => e.u32(TAG_OK)?.encode(v)?.ok(),
+            Envelope::Err(v) => e.u32(TAG_ERR)?.encode(v)?.ok(),
+            Envelope::InvalidMessage(msg) => e.u32(TAG_INVALID_MESSAGE)?.str(msg)?.ok(),
+            Envelope::AlreadyInstantiated => e.u32(TAG_ALREADY_INSTANTIATED)?.null()?.ok(),
+            Envelope::UnsupportedLayout(ver) => e.u32(TAG_UNSUPPORTED_LAYOUT)?.u8(*ver)?.ok(),
+            Envelope::NotInstantiated => e.u32(TAG_NOT_INSTANTIATED)?.null()?.ok(),
+            Envelope::StorageError(err) => e.u32(TAG_STORAGE_ERROR)?.str(err)?.ok(),
+        }
+    }
+}
+
+impl<'b, R: Decode<'b>, E: Decode<'b>> Decode<'b> for Envelope<R, E> {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if Some(2) != d.array()? {
+            return Err(decode::Error::Message("expected enum (2-element array)"));
+        }
+        match d.u32()? {
+            TAG_OK => R::decode(d).map(Envelope::Ok),
+            TAG_ERR => E::decode(d).map(Envelope::Err),
+            TAG_INVALID_MESSAGE => Ok(Envelope::InvalidMessage(d.str()?.into())),
+            TAG_ALREADY_INSTANTIATED => {
+                d.null()?;
+                Ok(Envelope::AlreadyInstantiated)
+            }
+            TAG_UNSUPPORTED_LAYOUT => Ok(Envelope::UnsupportedLayout(d.u8()?)),
+            TAG_NOT_INSTANTIATED => {
+                d.null()?;
+                Ok(Envelope::NotInstantiated)
+            }
+            TAG_STORAGE_ERROR => Ok(Envelope::StorageError(d.str()?.into())),
+            n => Err(decode::Error::UnknownVariant(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_result_compatibility() {
+        let mut buf_1 = Vec::new();
+        let mut buf_2 = Vec::new();
+        minicbor::encode(Envelope::<i32, i32>::Ok(1), &mut buf_1).unwrap();
+        minicbor::encode(Result::<i32, i32>::Ok(1), &mut buf_2).unwrap();
+        assert_eq!(buf_1, buf_2);
+
+        let mut buf_1 = Vec::new();
+        let mut buf_2 = Vec::new();
+        minicbor::encode(Envelope::<i32, i32>::Err(2), &mut buf_1).unwrap();
+        minicbor::encode(Result::<i32, i32>::Err(2), &mut buf_2).unwrap();
+        assert_eq!(buf_1, buf_2);
+    }
+
+    #[test]
+    fn test_invalid_message() {
+        let env = Envelope::<(), ()>::InvalidMessage("foo".into());
+        let mut buf = Vec::new();
+        minicbor::encode(&env, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x82, 0x02, 0x63, 0x66, 0x6f, 0x6f]); // http://cbor.me/?bytes=820263666f6f
+
+        let decoded: Envelope<(), ()> = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, env);
+    }
+
+    #[test]
+    fn test_already_instantiated() {
+        let env = Envelope::<(), ()>::AlreadyInstantiated;
+        let mut buf = Vec::new();
+        minicbor::encode(&env, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x82, 0x03, 0xf6]); // http://cbor.me/?bytes=8203f6
+
+        let decoded: Envelope<(), ()> = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, env);
+    }
+
+    #[test]
+    fn test_unsupported_layout() {
+        let env = Envelope::<(), ()>::UnsupportedLayout(0);
+        let mut buf = Vec::new();
+        minicbor::encode(&env, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x82, 0x04, 0x00]); // http://cbor.me/?bytes=820400
+
+        let decoded: Envelope<(), ()> = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, env);
+    }
+
+    #[test]
+    fn test_not_instantiated() {
+        let env = Envelope::<(), ()>::NotInstantiated;
+        let mut buf = Vec::new();
+        minicbor::encode(&env, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x82, 0x05, 0xf6]); // http://cbor.me/?bytes=8205f6
+
+        let decoded: Envelope<(), ()> = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, env);
+    }
+
+    #[test]
+    fn test_storage_error() {
+        let env = Envelope::<(), ()>::StorageError("foo".into());
+        let mut buf = Vec::new();
+        minicbor::encode(&env, &mut buf).unwrap();
+        assert_eq!(buf, vec![0x82, 0x06, 0x63, 0x66, 0x6f, 0x6f]); // http://cbor.me/?bytes=820663666f6f
+
+        let decoded: Envelope<(), ()> = minicbor::decode(&buf).unwrap();
+        assert_eq!(decoded, env);
+    }
+
+    #[test]
+    fn test_unknown_tag() {
+        let buf = vec![0x82, 0x0f, 0x00];
+        assert!(minicbor::decode::<Envelope<i32, i32>>(&buf).is_err());
+    }
+}
@@ -1,13 +1,39 @@
 //! Define the Host error.
 
+use alloc::string::String;
 use core::fmt::{self, Debug};
 
+/// The callee contract doesn't exist.
+pub const ERR_CONTRACT_NOT_FOUND: i32 = 1;
+/// The callee contract is already in the call stack and can't be processed again.
+pub const ERR_REENTRANCY: i32 = 2;
+/// The depth of nested contract calls exceeds `MAX_CALL_DEPTH`.
+pub const ERR_CALL_DEPTH_EXCEEDED: i32 = 3;
+/// A process call is made while executing a query.
+pub const ERR_READ_ONLY: i32 = 4;
+/// The balance of the contract is not enough for the transfer.
+pub const ERR_INSUFFICIENT_FUNDS: i32 = 5;
+/// The call ran out of gas.
+pub const ERR_OUT_OF_GAS: i32 = 6;
+/// The signature is not valid.
+pub const ERR_INVALID_SIGNATURE: i32 = 7;
+/// The pointer is not allocated by the contract or it is freed before.
+pub const ERR_INVALID_POINTER: i32 = 8;
+/// A sub-message of the response failed, therefore the call is reverted.
+pub const ERR_SUB_MESSAGE_FAILED: i32 = 9;
+
 ///
 pub enum Error {
     /// Error raised by the host
     HostError(i32),
     /// Generic error
     GenericError(&'static str),
+    /// The callee contract couldn't decode the message.
+    /// It contains the reason that is reported by the callee.
+    InvalidMessage(String),
+    /// Kelk couldn't read or write its records in the storage of the callee contract.
+    /// It contains the storage error that is reported by the callee.
+    StorageError(String),
 }
 
 /// Error raised by the host
@@ -21,6 +47,14 @@ impl Debug for Error {
         match self {
             Error::HostError(code) => f.debug_struct("HostError").field("code", code).finish(),
             Error::GenericError(msg) => f.debug_struct("GenericError").field("msg", msg).finish(),
+            Error::InvalidMessage(reason) => f
+                .debug_struct("InvalidMessage")
+                .field("reason", reason)
+                .finish(),
+            Error::StorageError(reason) => f
+                .debug_struct("StorageError")
+                .field("reason", reason)
+                .finish(),
         }
     }
 }
@@ -29,6 +63,11 @@ impl fmt::Display for Error {
         match self {
             Error::HostError(code) => write!(f, "host error code: {:?}", code),
             Error::GenericError(msg) => write!(f, "generic code: {:?}", msg),
+            Error::InvalidMessage(reason) => write!(f, "invalid message: {}", reason),
+            Error::StorageError(reason) => write!(f, "storage error: {}", reason),
         }
     }
 }
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
@@ -1,72 +1,155 @@
 //! Kelk public API
 //!
-//! `do_deploy`, `do_process` and `do_query`
+//! `do_instantiate`, `do_process`, `do_query` and `do_migrate`
 //! should be wrapped with a extern "C" entry point including
 //! the contract-specific function pointer.
 //! This is done via the `#[entry_point]` macro attribute.
+//!
+//! They read the message from the wasm memory and execute the entry points
+//! in [`entry`] with the host context. The result is passed back to the host
+//! as an encoded [`Envelope`](crate::envelope::Envelope).
 
 use crate::context::{Context, OwnedContext};
+use crate::crypto::Crypto;
+use crate::entry;
+use crate::error::ERR_INVALID_POINTER;
 use crate::import::ContextExt;
 use crate::memory::Pointer;
+use crate::metadata::ContractVersion;
+use crate::response::Response;
 use crate::storage::Storage;
+use crate::wasm_metadata::{ABI_VERSION, KEY_ABI_VERSION, KEY_KELK_VERSION};
 use alloc::boxed::Box;
+use alloc::string::ToString;
+use alloc::vec::Vec;
 use minicbor::{Decode, Encode};
 
+crate::__wasm_metadata_entry!(
+    KELK_VERSION_ENTRY,
+    KEY_KELK_VERSION,
+    env!("CARGO_PKG_VERSION").as_bytes()
+);
+crate::__wasm_metadata_entry!(
+    ABI_VERSION_ENTRY,
+    KEY_ABI_VERSION,
+    &ABI_VERSION.to_le_bytes()
+);
+
+/// kelk_interface_version returns the version of the interface between the host and the contract.
+/// The host should check it before calling the other exports.
+#[no_mangle]
+extern "C" fn kelk_interface_version() -> u32 {
+    ABI_VERSION
+}
+
 /// allocate reserves the given number of bytes in wasm memory and returns a pointer
 /// to a Pointer defining this data. This space is managed by the calling process
 /// and should be accompanied by a corresponding deallocate
 #[no_mangle]
 extern "C" fn allocate(size: u32) -> u64 {
+    #[cfg(all(feature = "runtime", not(test)))]
+    crate::runtime::reset_arena();
+
     Pointer::allocate(size).as_u64()
 }
 
 /// deallocate expects a pointer to a Pointer created with allocate.
 /// It will free both the Pointer and the memory referenced by the Pointer.
+/// It returns zero on success, or `ERR_INVALID_POINTER` if the pointer is not
+/// allocated by the contract or it is freed before.
 #[no_mangle]
-extern "C" fn deallocate(ptr_u64: u64) {
-    Pointer::from_u64(ptr_u64).deallocate();
+extern "C" fn deallocate(ptr_u64: u64) -> i32 {
+    let res = match Pointer::from_u64(ptr_u64).and_then(Pointer::deallocate) {
+        Ok(()) => 0,
+        Err(_) => ERR_INVALID_POINTER,
+    };
+
+    #[cfg(all(feature = "runtime", not(test)))]
+    crate::runtime::reset_arena();
+
+    res
 }
 
 /// do_instantiate should be wrapped in an external "C" export,
 /// containing a contract-specific function as arg.
-pub fn do_instantiate<'a, D: Decode<'a>, E: Encode>(
-    instantiate_fn: &dyn Fn(Context, D) -> Result<(), E>,
+/// See [`entry::instantiate`].
+pub fn do_instantiate<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    instantiate_fn: &dyn Fn(Context, D) -> Result<R, E>,
+    name: &str,
+    version: &str,
+    msg_ptr: u64,
+) -> u64 {
+    do_execute(
+        |ctx, msg| entry::instantiate(instantiate_fn, name, version, ctx, msg),
+        msg_ptr,
+    )
+}
+
+/// do_migrate should be wrapped in an external "C" export,
+/// containing a contract-specific function as arg.
+/// See [`entry::migrate`].
+#[allow(clippy::type_complexity)]
+pub fn do_migrate<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    migrate_fn: &dyn Fn(Context, Option<ContractVersion>, D) -> Result<R, E>,
+    name: &str,
+    version: &str,
     msg_ptr: u64,
 ) -> u64 {
-    do_execute(instantiate_fn, msg_ptr)
+    do_execute(
+        |ctx, msg| entry::migrate(migrate_fn, name, version, ctx, msg),
+        msg_ptr,
+    )
 }
 
 /// do_process should be wrapped in an external "C" export,
 /// containing a contract-specific function as arg.
-pub fn do_process<'a, D: Decode<'a>, E: Encode>(
-    process_fn: &dyn Fn(Context, D) -> Result<(), E>,
+/// See [`entry::process`].
+pub fn do_process<'a, D: Decode<'a>, R: Into<Response>, E: Encode>(
+    process_fn: &dyn Fn(Context, D) -> Result<R, E>,
     msg_ptr: u64,
 ) -> u64 {
-    do_execute(process_fn, msg_ptr)
+    do_execute(|ctx, msg| entry::process(process_fn, ctx, msg), msg_ptr)
 }
 
 /// do_query should be wrapped in an external "C" export,
 /// containing a contract-specific function as arg.
+/// See [`entry::query`].
 pub fn do_query<'a, D: Decode<'a>, R: Encode, E: Encode>(
     query_fn: &dyn Fn(Context, D) -> Result<R, E>,
     msg_ptr: u64,
 ) -> u64 {
-    do_execute(query_fn, msg_ptr)
+    do_execute(|ctx, msg| entry::query(query_fn, ctx, msg), msg_ptr)
 }
 
-fn do_execute<'a, D: Decode<'a>, R: Encode, E: Encode>(
-    func: &dyn Fn(Context, D) -> Result<R, E>,
-    msg_ptr: u64,
-) -> u64 {
-    let ptr = Pointer::from_u64(msg_ptr);
-    let buf = unsafe { ptr.to_slice() };
-    let msg = minicbor::decode(buf).expect("Decoding failed");
-    let ctx = make_context();
-    let res = func(ctx.as_ref(), msg);
-    let mut vec = crate::alloc::vec::Vec::new();
-    minicbor::encode(res, &mut vec).expect("Encoding failed");
-
-    Pointer::release_buffer(vec).as_u64()
+/// reads the message from the wasm memory, executes the entry with the host context
+/// and passes the result to the host.
+fn do_execute<'a>(exec: impl FnOnce(Context, &'a [u8]) -> Vec<u8>, msg_ptr: u64) -> u64 {
+    do_call(
+        |msg| {
+            let ctx = make_context();
+            exec(ctx.as_ref(), msg)
+        },
+        msg_ptr,
+    )
+}
+
+/// reads the message from the wasm memory, executes the function on it
+/// and passes the result to the host.
+/// If the pointer is not valid, `Envelope::InvalidMessage` is passed instead.
+fn do_call<'a>(exec: impl FnOnce(&'a [u8]) -> Vec<u8>, msg_ptr: u64) -> u64 {
+    #[cfg(all(feature = "runtime", not(test)))]
+    crate::runtime::begin_call();
+
+    let vec = match Pointer::from_u64(msg_ptr) {
+        Ok(ptr) => exec(unsafe { ptr.to_slice() }),
+        Err(err) => entry::invalid_message(&err.to_string()),
+    };
+    let ptr = Pointer::release_buffer(vec).as_u64();
+
+    #[cfg(all(feature = "runtime", not(test)))]
+    crate::runtime::end_call();
+
+    ptr
 }
 
 /// Make context instance
@@ -74,6 +157,7 @@ pub(crate) fn make_context() -> OwnedContext<ContextExt> {
     OwnedContext {
         blockchain: ContextExt::new(),
         storage: Storage::new(Box::new(ContextExt::new())),
+        crypto: Crypto::new(Box::new(ContextExt::new())),
     }
 }
 
@@ -81,102 +165,62 @@ pub(crate) fn make_context() -> OwnedContext<ContextExt> {
 mod tests {
     use super::*;
     use crate::alloc::vec;
+    use crate::envelope::Envelope;
     use wasm_bindgen_test::*;
 
-    // Uncomment this test if should_panic supported by wasm_bindgen_test.
-    // https://github.com/rustwasm/wasm-bindgen/issues/2286
-    //
-    // #[wasm_bindgen_test]
-    // #[should_panic]
-    // fn test_allocation() {
-    //     let ptr = allocate(1);
-    //     deallocate(ptr);
-
-    //     // Should panic here, because the pointer is freed before
-    //     deallocate(ptr);
-    // }
-
     #[wasm_bindgen_test]
-    fn test_instantiate() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
-
-        let res_ptr = do_instantiate(
-            &|_: Context, _: i32| -> Result<(), i32> { Ok(()) },
-            msg_ptr.as_u64(),
-        );
+    fn test_allocation() {
+        let ptr = allocate(1);
+        assert_eq!(deallocate(ptr), 0);
 
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x00, 0x80]); // Result::Ok(()) -> http://cbor.me/?bytes=820080
+        // The pointer is freed before
+        assert_eq!(deallocate(ptr), ERR_INVALID_POINTER);
+        assert_eq!(deallocate(0x0123456789abcdef), ERR_INVALID_POINTER);
     }
 
     #[wasm_bindgen_test]
-    fn test_instantiate_error() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
-
-        let res_ptr = do_instantiate(
-            &|_: Context, _: i32| -> Result<(), i32> { Err(0x0e) },
-            msg_ptr.as_u64(),
-        );
-
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+    fn test_interface_version() {
+        assert_eq!(kelk_interface_version(), ABI_VERSION);
     }
 
-    #[wasm_bindgen_test]
-    fn test_process() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
-
-        let res_ptr = do_process(
-            &|_: Context, _: i32| -> Result<(), i32> { Ok(()) },
-            msg_ptr.as_u64(),
-        );
-
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x00, 0x80]); // Result::Ok(()) -> http://cbor.me/?bytes=820080
-    }
+    // The tests don't call the host functions, since the host is not available.
 
     #[wasm_bindgen_test]
-    fn test_process_error() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
+    fn test_invalid_pointer() {
+        let res_ptr = do_call(|_| panic!("should not be called"), 0x0123456789abcdef);
 
-        let res_ptr = do_process(
-            &|_: Context, _: i32| -> Result<(), i32> { Err(0x0e) },
-            msg_ptr.as_u64(),
-        );
-
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+        let res_data = unsafe { Pointer::from_u64(res_ptr).unwrap().to_slice() };
+        let res: Envelope<(), i32> = minicbor::decode(res_data).unwrap();
+        assert!(matches!(res, Envelope::InvalidMessage(_)));
+        assert_eq!(deallocate(res_ptr), 0);
     }
 
     #[wasm_bindgen_test]
-    fn test_query() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
+    fn test_freed_pointer() {
+        let msg_ptr = Pointer::release_buffer(vec![0x00]).as_u64();
+        assert_eq!(deallocate(msg_ptr), 0);
 
-        let res_ptr = do_query(
-            &|_: Context, _: i32| -> Result<&str, i32> { Ok("foo") },
-            msg_ptr.as_u64(),
-        );
+        let res_ptr = do_call(|_| panic!("should not be called"), msg_ptr);
 
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x00, 0x63, 0x66, 0x6f, 0x6f]); // Result::Ok("foo") -> http://cbor.me/?bytes=0x820063666f6f
+        let res_data = unsafe { Pointer::from_u64(res_ptr).unwrap().to_slice() };
+        let res: Envelope<(), i32> = minicbor::decode(res_data).unwrap();
+        assert!(matches!(res, Envelope::InvalidMessage(_)));
+        assert_eq!(deallocate(res_ptr), 0);
     }
 
     #[wasm_bindgen_test]
-    fn test_query_error() {
-        let msg_data = vec![0x00]; // http://cbor.me/?bytes=8100
-        let msg_ptr = Pointer::release_buffer(msg_data);
-
-        let res_ptr = do_query(
-            &|_: Context, _: i32| -> Result<&str, i32> { Err(0x0e) },
-            msg_ptr.as_u64(),
-        );
-
-        let res_data = unsafe { Pointer::from_u64(res_ptr).to_slice() };
-        assert_eq!(res_data, vec![0x82, 0x01, 0x0e]); // Result::Err(0x0e) -> http://cbor.me/?bytes=82010e
+    fn test_result_pointer() {
+        let msg_ptr = Pointer::release_buffer(vec![0x00]).as_u64();
+
+        let res_ptr = do_call(|msg| msg.to_vec(), msg_ptr);
+        let res_data = unsafe { Pointer::from_u64(res_ptr).unwrap().to_slice() };
+        assert_eq!(res_data, [0x00]);
+
+        // The host owns both buffers and frees each of them once.
+        assert_ne!(res_ptr, msg_ptr);
+        assert_eq!(deallocate(msg_ptr), 0);
+        assert_eq!(deallocate(res_ptr), 0);
+        assert_eq!(deallocate(msg_ptr), ERR_INVALID_POINTER);
+        assert_eq!(deallocate(res_ptr), ERR_INVALID_POINTER);
     }
 }
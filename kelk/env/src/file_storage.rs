@@ -0,0 +1,185 @@
+//! Storage backed by a file on disk
+//!
+//! Unlike the mocked storage, the state is kept in a file, so the host tools and the local
+//! runners can persist the state of a contract between the runs and inspect it afterwards.
+//! The file has the same layout as the storage file on the chain, including the reserved area.
+//!
+//! This module requires the `std` feature.
+
+use crate::error::Error;
+use crate::storage::{Storage, StorageAPI, RESERVED_SIZE};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// opens the storage file at the given path, or creates it if it doesn't exist.
+/// The storage has `storage_size` bytes for the contract besides the reserved area.
+pub fn file_storage<P: AsRef<Path>>(path: P, storage_size: u32) -> io::Result<Storage> {
+    let api = FileStorageAPI::open(path, RESERVED_SIZE + storage_size)?;
+    Ok(Storage::new(Box::new(api)))
+}
+
+/// `FileStorageAPI` implements the storage APIs by a file on disk.
+///
+/// By default the size of the storage is fixed and accessing beyond it fails,
+/// like the storage on the chain. A growable storage extends the file on writes,
+/// up to its maximum size, and the bytes that are never written are read as zero.
+pub struct FileStorageAPI {
+    file: RefCell<File>,
+    len: Cell<u32>,
+    max_len: u32,
+}
+
+impl FileStorageAPI {
+    /// opens the storage file at the given path, or creates it if it doesn't exist.
+    /// The file is extended to `size` bytes if it is smaller.
+    pub fn open<P: AsRef<Path>>(path: P, size: u32) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+        let len = u32::try_from(file_len)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "storage file is too large"))?;
+        if len < size {
+            file.set_len(size as u64)?;
+        }
+        let len = len.max(size);
+        Ok(Self {
+            file: RefCell::new(file),
+            len: Cell::new(len),
+            max_len: len,
+        })
+    }
+
+    /// lets the storage grow up to `max_size` bytes
+    pub fn growable(mut self, max_size: u32) -> Self {
+        self.max_len = max_size.max(self.len.get());
+        self
+    }
+
+    /// returns the current size of the storage file
+    pub fn len(&self) -> u32 {
+        self.len.get()
+    }
+
+    /// returns true if the storage file is empty
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// returns the maximum size of the storage file
+    pub fn max_len(&self) -> u32 {
+        self.max_len
+    }
+
+    /// flushes the written data to the disk
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.borrow().sync_data()
+    }
+
+    fn end(offset: u32, len: usize) -> Result<u32, Error> {
+        u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .ok_or(Error::GenericError("overflowed"))
+    }
+}
+
+impl StorageAPI for FileStorageAPI {
+    fn write(&self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        let end = Self::end(offset, data.len())?;
+        if end > self.max_len {
+            return Err(Error::GenericError("overflowed"));
+        }
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.write_all(data))
+            .map_err(|_| Error::GenericError("failed to write the storage file"))?;
+        if end > self.len.get() {
+            self.len.set(end);
+        }
+        Ok(())
+    }
+
+    fn read(&self, offset: u32, len: u32) -> Result<Vec<u8>, Error> {
+        let end = Self::end(offset, len as usize)?;
+        if end > self.max_len {
+            return Err(Error::GenericError("overflowed"));
+        }
+        // The bytes after the end of a growable file are zero.
+        let mut buf = vec![0; len as usize];
+        let available = self.len.get().saturating_sub(offset).min(len) as usize;
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset as u64))
+            .and_then(|_| file.read_exact(&mut buf[..available]))
+            .map_err(|_| Error::GenericError("failed to read the storage file"))?;
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(alloc::format!(
+            "kelk-{}-{}.storage",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_persist() {
+        let path = temp_path("persist");
+        {
+            let storage = file_storage(&path, 16).unwrap();
+            storage.write_u32(0, 1).unwrap();
+            storage.write_u64(8, 2).unwrap();
+            assert!(storage.write_u64(12, 3).is_err());
+        }
+        let storage = file_storage(&path, 16).unwrap();
+        assert_eq!(storage.read_u32(0).unwrap(), 1);
+        assert_eq!(storage.read_u64(8).unwrap(), 2);
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            (RESERVED_SIZE + 16) as u64
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_growable() {
+        let path = temp_path("growable");
+        let api = FileStorageAPI::open(&path, 8).unwrap().growable(32);
+        assert_eq!(api.len(), 8);
+
+        assert_eq!(api.read(4, 8).unwrap(), [0; 8]);
+        api.write(20, &[1, 2]).unwrap();
+        assert_eq!(api.len(), 22);
+        assert_eq!(api.read(19, 4).unwrap(), [0, 1, 2, 0]);
+        assert!(api.write(31, &[1, 2]).is_err());
+        assert!(api.read(u32::MAX, 2).is_err());
+        api.sync().unwrap();
+        drop(api);
+
+        // The file is reopened with its grown size.
+        let api = FileStorageAPI::open(&path, 8).unwrap();
+        assert_eq!(api.len(), 22);
+        assert_eq!(api.read(20, 2).unwrap(), [1, 2]);
+        assert!(api.write(22, &[1]).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,11 +1,17 @@
+use crate::address::Address;
 use crate::alloc::vec::Vec;
-use crate::blockchain::Blockchain;
-use crate::error::Error;
+use crate::blockchain::{Amount, Blockchain};
+use crate::crypto::{
+    CryptoAPI, Hash, BLS_PUBLIC_KEY_SIZE, BLS_SIGNATURE_SIZE, ED25519_PUBLIC_KEY_SIZE,
+    ED25519_SIGNATURE_SIZE,
+};
+use crate::error::{Error, ERR_INVALID_SIGNATURE};
+use crate::memory::Pointer;
 use crate::params::*;
 use crate::storage::StorageAPI;
 
-#[cfg(not(test))]
-#[link(wasm_import_module = "zarb")]
+// The module name should be the same as `wasm_metadata::IMPORT_MODULE`.
+#[link(wasm_import_module = "zarb_v2")]
 extern "C" {
     /// write data at given offset of storage file.
     /// `ptr` is the location in sandbox memory where data should be read from.
@@ -15,6 +21,56 @@ extern "C" {
     /// `ptr` is the location in sandbox memory where data should be written to.
     /// `len` is the length of data.
     fn read_storage(offset: u32, ptr: u32, len: u32) -> i32;
+    /// queries the contract at the given address.
+    /// `addr_ptr` is the location of the callee's address in sandbox memory.
+    /// `msg_ptr` and `msg_len` define the CBOR encoded message.
+    /// The host allocates the result through `allocate` and writes its pointer at `res_ptr`.
+    fn query_contract(addr_ptr: u32, msg_ptr: u32, msg_len: u32, res_ptr: u32) -> i32;
+    /// calls the process entry of the contract at the given address.
+    /// `addr_ptr` is the location of the callee's address in sandbox memory.
+    /// `msg_ptr` and `msg_len` define the CBOR encoded message.
+    /// The host allocates the result through `allocate` and writes its pointer at `res_ptr`.
+    fn process_contract(addr_ptr: u32, msg_ptr: u32, msg_len: u32, res_ptr: u32) -> i32;
+    /// writes the amount of coins attached to the current call at `amount_ptr`.
+    /// The amount is a 16-byte big-endian unsigned integer.
+    fn get_attached_amount(amount_ptr: u32) -> i32;
+    /// writes the balance of the contract at `amount_ptr`.
+    /// The amount is a 16-byte big-endian unsigned integer.
+    fn get_balance(amount_ptr: u32) -> i32;
+    /// transfers coins from the contract's balance to the given address.
+    /// `addr_ptr` is the location of the receiver's address in sandbox memory.
+    /// `amount_ptr` is the location of the amount as a 16-byte big-endian unsigned integer.
+    fn transfer(addr_ptr: u32, amount_ptr: u32) -> i32;
+    /// returns the amount of gas left for the current call.
+    fn gas_remaining() -> u64;
+    /// consumes the given amount of gas.
+    fn consume_gas(amount: u64) -> i32;
+    /// returns the number of the current block.
+    fn block_number() -> u64;
+    /// calculates the Blake2b-256 hash of data and writes it at `hash_ptr`.
+    fn hash_blake2b_256(data_ptr: u32, data_len: u32, hash_ptr: u32) -> i32;
+    /// calculates the SHA-256 hash of data and writes it at `hash_ptr`.
+    fn hash_sha256(data_ptr: u32, data_len: u32, hash_ptr: u32) -> i32;
+    /// verifies the Ed25519 signature. `pk_ptr` points to a 32-byte public key
+    /// and `sig_ptr` points to a 64-byte signature.
+    /// It returns `ERR_INVALID_SIGNATURE` if the signature is not valid.
+    fn verify_ed25519(pk_ptr: u32, msg_ptr: u32, msg_len: u32, sig_ptr: u32) -> i32;
+    /// verifies the BLS signature. `pk_ptr` points to a 96-byte compressed public key
+    /// and `sig_ptr` points to a 48-byte compressed signature.
+    /// It returns `ERR_INVALID_SIGNATURE` if the signature is not valid.
+    fn verify_bls(pk_ptr: u32, msg_ptr: u32, msg_len: u32, sig_ptr: u32) -> i32;
+    /// reports a panic to the host right before the contract traps.
+    /// `msg_ptr` and `msg_len` define the panic message,
+    /// `file_ptr` and `file_len` define the source file where the panic occurred.
+    #[cfg(all(feature = "panic-handler", not(feature = "std"), not(test)))]
+    fn report_panic(
+        msg_ptr: u32,
+        msg_len: u32,
+        file_ptr: u32,
+        file_len: u32,
+        line: u32,
+        column: u32,
+    );
 }
 
 pub(crate) struct ContextExt {}
@@ -25,16 +81,19 @@ impl ContextExt {
     }
 }
 
-// TODO:
-// Is it possible to create a zarb module for testing and remove these code?
-#[cfg(test)]
-pub unsafe fn write_storage(_offset: u32, _ptr: u32, _len: u32) -> i32 {
-    0
-}
-
-#[cfg(test)]
-pub unsafe fn read_storage(_offset: u32, _ptr: u32, _len: u32) -> i32 {
-    0
+/// reports the panic message and its location to the host.
+#[cfg(all(feature = "panic-handler", not(feature = "std"), not(test)))]
+pub(crate) fn panic(msg: &str, file: &str, line: u32, column: u32) {
+    unsafe {
+        report_panic(
+            msg.as_ptr() as u32,
+            msg.len() as u32,
+            file.as_ptr() as u32,
+            file.len() as u32,
+            line,
+            column,
+        )
+    }
 }
 
 impl StorageAPI for ContextExt {
@@ -66,4 +125,153 @@ impl Blockchain for ContextExt {
     fn get_param(&self, _param_id: i32) -> Option<ParamType> {
         unimplemented!();
     }
+
+    fn query_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut res_ptr: u64 = 0;
+        let code = unsafe {
+            query_contract(
+                addr.as_bytes().as_ptr() as u32,
+                msg.as_ptr() as u32,
+                msg.len() as u32,
+                core::ptr::addr_of_mut!(res_ptr) as u32,
+            )
+        };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Pointer::from_u64(res_ptr)?.into_vec()
+    }
+
+    fn process_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut res_ptr: u64 = 0;
+        let code = unsafe {
+            process_contract(
+                addr.as_bytes().as_ptr() as u32,
+                msg.as_ptr() as u32,
+                msg.len() as u32,
+                core::ptr::addr_of_mut!(res_ptr) as u32,
+            )
+        };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Pointer::from_u64(res_ptr)?.into_vec()
+    }
+
+    fn attached_amount(&self) -> Result<Amount, Error> {
+        let mut buf = [0u8; 16];
+        let code = unsafe { get_attached_amount(buf.as_mut_ptr() as u32) };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(Amount::from_be_bytes(buf))
+    }
+
+    fn balance(&self) -> Result<Amount, Error> {
+        let mut buf = [0u8; 16];
+        let code = unsafe { get_balance(buf.as_mut_ptr() as u32) };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(Amount::from_be_bytes(buf))
+    }
+
+    fn transfer(&self, to: &Address, amount: Amount) -> Result<(), Error> {
+        let buf = amount.to_be_bytes();
+        let code = unsafe { transfer(to.as_bytes().as_ptr() as u32, buf.as_ptr() as u32) };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(())
+    }
+
+    fn gas_remaining(&self) -> u64 {
+        unsafe { gas_remaining() }
+    }
+
+    fn consume_gas(&self, amount: u64) -> Result<(), Error> {
+        let code = unsafe { consume_gas(amount) };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(())
+    }
+
+    fn block_number(&self) -> u64 {
+        unsafe { block_number() }
+    }
+}
+
+impl CryptoAPI for ContextExt {
+    fn blake2b_256(&self, data: &[u8]) -> Result<Hash, Error> {
+        let mut hash = [0u8; 32];
+        let code = unsafe {
+            hash_blake2b_256(
+                data.as_ptr() as u32,
+                data.len() as u32,
+                hash.as_mut_ptr() as u32,
+            )
+        };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(hash)
+    }
+
+    fn sha256(&self, data: &[u8]) -> Result<Hash, Error> {
+        let mut hash = [0u8; 32];
+        let code = unsafe {
+            hash_sha256(
+                data.as_ptr() as u32,
+                data.len() as u32,
+                hash.as_mut_ptr() as u32,
+            )
+        };
+        if code != 0 {
+            return Err(Error::HostError(code));
+        }
+        Ok(hash)
+    }
+
+    fn verify_ed25519(
+        &self,
+        public_key: &[u8; ED25519_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; ED25519_SIGNATURE_SIZE],
+    ) -> Result<bool, Error> {
+        let code = unsafe {
+            verify_ed25519(
+                public_key.as_ptr() as u32,
+                msg.as_ptr() as u32,
+                msg.len() as u32,
+                signature.as_ptr() as u32,
+            )
+        };
+        match code {
+            0 => Ok(true),
+            ERR_INVALID_SIGNATURE => Ok(false),
+            _ => Err(Error::HostError(code)),
+        }
+    }
+
+    fn verify_bls(
+        &self,
+        public_key: &[u8; BLS_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; BLS_SIGNATURE_SIZE],
+    ) -> Result<bool, Error> {
+        let code = unsafe {
+            verify_bls(
+                public_key.as_ptr() as u32,
+                msg.as_ptr() as u32,
+                msg.len() as u32,
+                signature.as_ptr() as u32,
+            )
+        };
+        match code {
+            0 => Ok(true),
+            ERR_INVALID_SIGNATURE => Ok(false),
+            _ => Err(Error::HostError(code)),
+        }
+    }
 }
@@ -23,12 +23,25 @@
     unused_extern_crates
 )]
 
+pub mod address;
+pub mod allocator;
+pub mod amount;
 pub mod blockchain;
 pub mod context;
+pub mod crypto;
+pub mod entry;
+pub mod envelope;
 pub mod error;
+#[cfg(feature = "std")]
+pub mod file_storage;
+pub mod metadata;
+#[cfg(any(test, feature = "mock"))]
 pub mod mock;
 pub mod params;
+pub mod response;
+pub mod schema;
 pub mod storage;
+pub mod wasm_metadata;
 
 #[cfg(target_arch = "wasm32")]
 mod memory;
@@ -39,12 +52,30 @@ mod import;
 #[cfg(target_arch = "wasm32")]
 pub mod export;
 
+// The standard library provides its own panic handler.
+#[cfg(all(
+    target_arch = "wasm32",
+    feature = "panic-handler",
+    not(feature = "std"),
+    not(test)
+))]
+mod panic;
+
+#[cfg(all(target_arch = "wasm32", feature = "runtime", not(test)))]
+mod runtime;
+
 #[cfg(target_arch = "wasm32")]
-pub use crate::export::{do_instantiate, do_process, do_query};
+pub use crate::export::{do_instantiate, do_migrate, do_process, do_query};
 
-pub use kelk_derive::kelk_derive;
+pub use kelk_derive::{kelk_contract, kelk_derive, Schema};
 
 #[macro_use]
 extern crate doc_comment;
 
+// Allows the derive macros to refer to `kelk_env` inside this crate.
+extern crate self as kelk_env;
+
 pub extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
@@ -1,5 +1,58 @@
+//! Memory pointers shared between the contract and the host
+//!
+//! The buffers that are handed out to the host by `allocate` or `release_buffer`
+//! are tracked until they are freed. A pointer that the host passes back is validated
+//! against them, so a bogus or double-freed pointer is refused instead of
+//! corrupting the heap.
+
 use crate::alloc;
 use crate::alloc::vec::Vec;
+use crate::error::Error;
+use core::cell::UnsafeCell;
+
+/// The buffers that are handed out and not freed yet, as pointer and length
+struct Registry(UnsafeCell<Vec<(usize, u32)>>);
+
+// Wasm contracts are single-threaded.
+unsafe impl Sync for Registry {}
+
+static LIVE_BUFFERS: Registry = Registry(UnsafeCell::new(Vec::new()));
+
+impl Registry {
+    #[allow(clippy::mut_from_ref)]
+    fn buffers(&self) -> &mut Vec<(usize, u32)> {
+        unsafe { &mut *self.0.get() }
+    }
+
+    fn insert(&self, ptr: usize, len: u32) {
+        self.buffers().push((ptr, len));
+    }
+
+    fn contains(&self, ptr: usize, len: u32) -> bool {
+        self.buffers().contains(&(ptr, len))
+    }
+
+    fn remove(&self, ptr: usize, len: u32) -> bool {
+        let buffers = self.buffers();
+        match buffers.iter().position(|b| *b == (ptr, len)) {
+            Some(pos) => {
+                buffers.swap_remove(pos);
+                if buffers.is_empty() {
+                    // Free the registry too, so the allocator can reset the arena.
+                    *buffers = Vec::new();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// returns true if a buffer is handed out to the host and it is not freed yet.
+#[cfg(all(target_arch = "wasm32", feature = "runtime", not(test)))]
+pub(crate) fn has_live_buffers() -> bool {
+    !LIVE_BUFFERS.buffers().is_empty()
+}
 
 /// defines a pointer to the allocated space in Wasm's linear memory.
 pub struct Pointer {
@@ -10,12 +63,17 @@ pub struct Pointer {
 }
 
 impl Pointer {
-    /// defiles a pointer from u64
-    pub fn from_u64(ptr_64: u64) -> Self {
+    /// defines a pointer from u64.
+    /// It fails if the pointer is not allocated by `allocate` or `release_buffer`,
+    /// or it is freed before.
+    pub fn from_u64(ptr_64: u64) -> Result<Self, Error> {
         let ptr = (ptr_64 & 0xFFFFFFFF) as *const u8;
         let len = (ptr_64 >> 32) as u32;
 
-        Self { ptr, len }
+        if !LIVE_BUFFERS.contains(ptr as usize, len) {
+            return Err(Error::GenericError("invalid pointer"));
+        }
+        Ok(Self { ptr, len })
     }
 
     /// defines the pointer as u64
@@ -33,12 +91,20 @@ impl Pointer {
     }
 
     /// frees the allocated memory
-    pub fn deallocate(self) {
-        let v = unsafe {
-            Vec::from_raw_parts(self.ptr as *mut u8, self.len as usize, self.len as usize)
-        };
+    pub fn deallocate(self) -> Result<(), Error> {
+        self.into_vec().map(core::mem::drop)
+    }
 
-        core::mem::drop(v);
+    /// takes the ownership of the allocated memory and converts it to a vector.
+    /// It fails if the memory is not allocated by `allocate` or `release_buffer`,
+    /// or it is freed before.
+    pub fn into_vec(self) -> Result<Vec<u8>, Error> {
+        if !LIVE_BUFFERS.remove(self.ptr as usize, self.len) {
+            return Err(Error::GenericError("invalid pointer"));
+        }
+        Ok(unsafe {
+            Vec::from_raw_parts(self.ptr as *mut u8, self.len as usize, self.len as usize)
+        })
     }
 
     /// releases the vector and keep the buffer as an allocated memory.
@@ -56,6 +122,7 @@ impl Pointer {
         let ptr = buffer.as_ptr();
 
         core::mem::forget(buffer);
+        LIVE_BUFFERS.insert(ptr as usize, len);
 
         Self { ptr, len }
     }
@@ -94,10 +161,30 @@ mod tests {
 
     #[wasm_bindgen_test]
     fn test_from_u64() {
-        let ptr_64: u64 = 0x0123456789abcdef;
-        let ptr = Pointer::from_u64(ptr_64);
+        let ptr_64 = Pointer::allocate(16).as_u64();
+        let ptr = Pointer::from_u64(ptr_64).unwrap();
+
+        assert_eq!(ptr.len, 16);
+        assert_eq!(ptr.ptr as u64, ptr_64 & 0xFFFFFFFF);
 
-        assert_eq!(ptr.len, 0x01234567);
-        assert_eq!(ptr.ptr as u32, 0x89abcdef);
+        // Wrong length
+        assert!(Pointer::from_u64(ptr_64 + (1 << 32)).is_err());
+        // Unknown pointer
+        assert!(Pointer::from_u64(0x0123456789abcdef).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_deallocate() {
+        let ptr_64 = Pointer::allocate(16).as_u64();
+        Pointer::from_u64(ptr_64).unwrap().deallocate().unwrap();
+
+        // Double free
+        assert!(Pointer::from_u64(ptr_64).is_err());
+        assert!(Pointer {
+            ptr: ptr_64 as u32 as *const u8,
+            len: 16
+        }
+        .deallocate()
+        .is_err());
     }
 }
@@ -0,0 +1,173 @@
+//! The metadata that Kelk keeps about the contract in the reserved area of the storage
+//!
+//! The metadata is written on instantiation and updated on migration.
+//! Queries can read it through [`read_metadata`] and tools can read it
+//! by wrapping the contract's storage file in a [`Storage`].
+//!
+//! The record is encoded in CBOR and prefixed by its length as a 16-bit integer.
+//! It is written after the layout header. A zero length means the record is not written yet.
+//! The storage with the legacy layout has no reserved area, therefore it has no metadata.
+
+use crate::error::Error;
+use crate::storage::{
+    Storage, LAYOUT_HEADER_SIZE, LAYOUT_VERSION, LEGACY_LAYOUT_VERSION, RESERVED_SIZE,
+};
+use crate::Schema;
+use alloc::string::String;
+use alloc::vec::Vec;
+use minicbor::{Decode, Encode};
+
+/// The version of Kelk that writes the metadata
+pub const KELK_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The offset of the metadata record in the reserved area
+const METADATA_OFFSET: u32 = LAYOUT_HEADER_SIZE;
+
+/// The name and the version of the contract
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct ContractVersion {
+    /// The name of the contract, like "calculator"
+    #[n(0)]
+    pub name: String,
+    /// The version of the contract, like "0.1.0"
+    #[n(1)]
+    pub version: String,
+}
+
+impl ContractVersion {
+    /// creates a new contract version
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+        }
+    }
+}
+
+/// The metadata of the contract
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct Metadata {
+    /// Whether the contract is instantiated
+    #[n(0)]
+    pub initialized: bool,
+    /// The version of Kelk that wrote the metadata
+    #[n(1)]
+    pub kelk_version: String,
+    /// The name and the version of the contract
+    #[n(2)]
+    pub contract: ContractVersion,
+    /// The number of the block that the contract was instantiated in
+    #[n(3)]
+    pub block: u64,
+}
+
+impl Metadata {
+    /// creates the metadata of a contract that is instantiated in the given block
+    pub fn new(contract: ContractVersion, block: u64) -> Self {
+        Self {
+            initialized: true,
+            kelk_version: KELK_VERSION.into(),
+            contract,
+            block,
+        }
+    }
+}
+
+/// reads the metadata of the contract.
+/// It returns `None` if the metadata is not written yet or the storage has the legacy layout.
+pub fn read_metadata(storage: &Storage) -> Result<Option<Metadata>, Error> {
+    match storage.layout_version()? {
+        LAYOUT_VERSION => {}
+        LEGACY_LAYOUT_VERSION => return Ok(None),
+        _ => return Err(Error::GenericError("unsupported storage layout")),
+    }
+    let len = u16::from_be_bytes(
+        storage
+            .read_reserved(METADATA_OFFSET, 2)?
+            .try_into()
+            .unwrap(),
+    );
+    if len == 0 {
+        return Ok(None);
+    }
+    let data = storage.read_reserved(METADATA_OFFSET + 2, len as u32)?;
+    let metadata =
+        minicbor::decode(&data).map_err(|_| Error::GenericError("invalid metadata record"))?;
+    Ok(Some(metadata))
+}
+
+/// writes the metadata of the contract.
+/// The storage should have the current layout.
+pub fn write_metadata(storage: &Storage, metadata: &Metadata) -> Result<(), Error> {
+    if storage.layout_version()? != LAYOUT_VERSION {
+        return Err(Error::GenericError("unsupported storage layout"));
+    }
+    let mut data = Vec::new();
+    minicbor::encode(metadata, &mut data).expect("Encoding failed");
+    if data.len() + 2 > (RESERVED_SIZE - METADATA_OFFSET) as usize {
+        return Err(Error::GenericError("metadata record is too long"));
+    }
+    storage.write_reserved(METADATA_OFFSET, &(data.len() as u16).to_be_bytes())?;
+    storage.write_reserved(METADATA_OFFSET + 2, &data)
+}
+
+/// returns true if the contract is already instantiated.
+pub fn is_instantiated(storage: &Storage) -> Result<bool, Error> {
+    Ok(read_metadata(storage)?.is_some_and(|m| m.initialized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{mock_storage, MockStorageAPI};
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_metadata() {
+        let storage = mock_storage(0);
+        assert_eq!(read_metadata(&storage).unwrap(), None);
+        assert!(!is_instantiated(&storage).unwrap());
+
+        let metadata = Metadata::new(ContractVersion::new("calculator", "0.1.0"), 7);
+        write_metadata(&storage, &metadata).unwrap();
+        assert_eq!(read_metadata(&storage).unwrap(), Some(metadata));
+        assert!(is_instantiated(&storage).unwrap());
+    }
+
+    #[test]
+    fn test_metadata_too_long() {
+        let storage = mock_storage(0);
+        let contract =
+            ContractVersion::new("a".repeat(RESERVED_SIZE as usize), "0.1.0".to_string());
+        assert!(write_metadata(&storage, &Metadata::new(contract, 1)).is_err());
+    }
+
+    #[test]
+    fn test_legacy_layout() {
+        let storage = Storage::new(Box::new(MockStorageAPI::new(RESERVED_SIZE as usize)));
+        // The data of the legacy contract looks like a metadata record.
+        storage
+            .api
+            .write(0, &[0, 4, 0xff, 0xff, 0xff, 0xff])
+            .unwrap();
+        storage
+            .api
+            .write(8, &[0, 4, 0xff, 0xff, 0xff, 0xff])
+            .unwrap();
+
+        assert_eq!(read_metadata(&storage).unwrap(), None);
+        let metadata = Metadata::new(ContractVersion::new("foo", "1.0.0"), 1);
+        assert!(write_metadata(&storage, &metadata).is_err());
+    }
+
+    #[test]
+    fn test_reserved_area() {
+        let storage = mock_storage(4);
+        let metadata = Metadata::new(ContractVersion::new("foo", "1.0.0"), 1);
+        write_metadata(&storage, &metadata).unwrap();
+        storage.write_u32(0, 0xffffffff).unwrap();
+
+        assert_eq!(read_metadata(&storage).unwrap(), Some(metadata));
+    }
+}
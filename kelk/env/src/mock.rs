@@ -1,51 +1,615 @@
 //! Mocking Context for testing contracts
 
-use crate::error::Error;
-use crate::storage::Storage;
+use crate::address::Address;
+use crate::blockchain::Amount;
+use crate::crypto::{
+    Crypto, CryptoAPI, Hash, BLS_DST, BLS_PUBLIC_KEY_SIZE, BLS_SIGNATURE_SIZE,
+    ED25519_PUBLIC_KEY_SIZE, ED25519_SIGNATURE_SIZE,
+};
+use crate::envelope::Envelope;
+use crate::error::{Error, ERR_CONTRACT_NOT_FOUND, ERR_INSUFFICIENT_FUNDS, ERR_OUT_OF_GAS};
+use crate::response::Response;
+use crate::storage::{layout_header, Storage, LAYOUT_HEADER_SIZE, RESERVED_SIZE};
 use crate::{
     blockchain::Blockchain, context::OwnedContext, params::ParamType, storage::StorageAPI,
 };
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::ToString;
 use alloc::vec::Vec;
-use core::cell::RefCell;
+use core::cell::{Cell, RefCell};
+use core::ops::Range;
+pub(crate) use expand_msg::ExpandMsgSha256;
+use minicbor::{Decode, Encode};
+
+pub mod chain;
+mod expand_msg;
+pub mod snapshot;
 
-/// mocks the context for testing
+pub use chain::{MockChain, MockContract};
+pub use snapshot::StorageSnapshot;
+
+/// mocks the context for testing.
+/// The storage has `storage_size` bytes for the contract besides the reserved area,
+/// and it has the current layout, like the storage of an instantiated contract.
+/// The gas is unlimited, but the used gas is accounted by the default cost model.
 pub fn mock_context(storage_size: usize) -> OwnedContext<MockBlockchain> {
-    OwnedContext {
-        blockchain: MockBlockchain::new(),
-        storage: mock_storage(storage_size),
-    }
+    mock_context_with_gas(storage_size, u64::MAX, GasCosts::default())
 }
 
-/// mocks the storage for testing
+/// mocks the context for testing with the given gas limit and cost model.
+/// The storage and the blockchain mocks share the same gas meter.
+pub fn mock_context_with_gas(
+    storage_size: usize,
+    gas_limit: u64,
+    costs: GasCosts,
+) -> OwnedContext<MockBlockchain> {
+    build_context(storage_size, gas_limit, costs).0
+}
+
+/// mocks the context for testing, like [`mock_context`],
+/// and returns the trace of the storage accesses.
+pub fn mock_context_with_trace(
+    storage_size: usize,
+) -> (OwnedContext<MockBlockchain>, Rc<StorageTrace>) {
+    build_context(storage_size, u64::MAX, GasCosts::default())
+}
+
+/// builds the mocked context with the current storage layout and a shared gas meter,
+/// and returns the trace of its storage
+fn build_context(
+    storage_size: usize,
+    gas_limit: u64,
+    costs: GasCosts,
+) -> (OwnedContext<MockBlockchain>, Rc<StorageTrace>) {
+    let gas_meter = Rc::new(MockGasMeter::new(gas_limit, costs));
+    let storage =
+        MockStorageAPI::with_gas_meter(RESERVED_SIZE as usize + storage_size, gas_meter.clone())
+            .with_layout();
+    let trace = storage.trace();
+    let ctx = OwnedContext {
+        blockchain: MockBlockchain::with_gas_meter(gas_meter),
+        storage: Storage::new(Box::new(storage)),
+        crypto: mock_crypto(),
+    };
+    (ctx, trace)
+}
+
+/// mocks the crypto for testing
+pub fn mock_crypto() -> Crypto {
+    Crypto::new(Box::new(MockCryptoAPI {}))
+}
+
+/// mocks the storage for testing.
+/// The storage file has `RESERVED_SIZE` more bytes for the reserved area
+/// and it has the current layout.
 pub fn mock_storage(storage_size: usize) -> Storage {
-    let storage = MockStorageAPI::new(storage_size);
-    Storage {
-        api: Box::new(storage),
+    let storage = MockStorageAPI::new(RESERVED_SIZE as usize + storage_size).with_layout();
+    Storage::new(Box::new(storage))
+}
+
+/// The cost model of the mocked gas meter.
+/// Storage reads and writes are charged by their base cost plus the cost of each byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasCosts {
+    /// The base cost of reading from storage
+    pub read_base: u64,
+    /// The cost of reading each byte from storage
+    pub read_per_byte: u64,
+    /// The base cost of writing into storage
+    pub write_base: u64,
+    /// The cost of writing each byte into storage
+    pub write_per_byte: u64,
+}
+
+impl GasCosts {
+    /// returns the cost of reading `len` bytes from storage,
+    /// or `None` if the cost overflows.
+    pub fn read_cost(&self, len: u32) -> Option<u64> {
+        self.read_per_byte
+            .checked_mul(len as u64)?
+            .checked_add(self.read_base)
+    }
+
+    /// returns the cost of writing `len` bytes into storage,
+    /// or `None` if the cost overflows.
+    pub fn write_cost(&self, len: u32) -> Option<u64> {
+        self.write_per_byte
+            .checked_mul(len as u64)?
+            .checked_add(self.write_base)
+    }
+}
+
+impl Default for GasCosts {
+    fn default() -> Self {
+        Self {
+            read_base: 100,
+            read_per_byte: 1,
+            write_base: 200,
+            write_per_byte: 2,
+        }
+    }
+}
+
+/// mocks the gas meter for testing purpose.
+pub struct MockGasMeter {
+    limit: u64,
+    used: Cell<u64>,
+    costs: GasCosts,
+}
+
+impl MockGasMeter {
+    /// instantiates a new gas meter with the given limit and cost model
+    pub fn new(limit: u64, costs: GasCosts) -> Self {
+        Self {
+            limit,
+            used: Cell::new(0),
+            costs,
+        }
+    }
+
+    /// returns the cost model of the gas meter
+    pub fn costs(&self) -> &GasCosts {
+        &self.costs
+    }
+
+    /// returns the amount of used gas
+    pub fn used(&self) -> u64 {
+        self.used.get()
+    }
+
+    /// returns the amount of gas left
+    pub fn remaining(&self) -> u64 {
+        self.limit - self.used.get()
+    }
+
+    /// consumes the given amount of gas.
+    /// If there is not enough gas left, all the remaining gas is consumed
+    /// and `ERR_OUT_OF_GAS` is returned.
+    pub fn consume(&self, amount: u64) -> Result<(), Error> {
+        if amount > self.remaining() {
+            return self.exhaust();
+        }
+        self.used.set(self.used.get() + amount);
+        Ok(())
+    }
+
+    /// consumes all the remaining gas and returns `ERR_OUT_OF_GAS`
+    fn exhaust(&self) -> Result<(), Error> {
+        self.used.set(self.limit);
+        Err(Error::HostError(ERR_OUT_OF_GAS))
+    }
+}
+
+/// An access to the storage that is recorded by [`StorageTrace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageAccess {
+    /// Reading `length` bytes at `offset`. `data` is empty if the read failed.
+    Read {
+        /// The offset of the read
+        offset: u32,
+        /// The number of bytes to read
+        length: u32,
+        /// The bytes that are read
+        data: Vec<u8>,
+    },
+    /// Writing `data` at `offset`
+    Write {
+        /// The offset of the write
+        offset: u32,
+        /// The bytes to write
+        data: Vec<u8>,
+    },
+}
+
+/// The trace of the storage accesses, in the order of the calls.
+/// All the calls to the storage are recorded, even the failed ones,
+/// since each of them is a call to the host.
+#[derive(Debug, Default)]
+pub struct StorageTrace {
+    accesses: RefCell<Vec<StorageAccess>>,
+}
+
+impl StorageTrace {
+    /// returns the recorded accesses
+    pub fn accesses(&self) -> Vec<StorageAccess> {
+        self.accesses.borrow().clone()
+    }
+
+    /// returns the number of the reads
+    pub fn reads(&self) -> usize {
+        self.accesses
+            .borrow()
+            .iter()
+            .filter(|access| matches!(access, StorageAccess::Read { .. }))
+            .count()
+    }
+
+    /// returns the number of the writes
+    pub fn writes(&self) -> usize {
+        self.accesses
+            .borrow()
+            .iter()
+            .filter(|access| matches!(access, StorageAccess::Write { .. }))
+            .count()
+    }
+
+    /// returns the total number of the requested bytes by the reads
+    pub fn bytes_read(&self) -> u64 {
+        self.accesses
+            .borrow()
+            .iter()
+            .map(|access| match access {
+                StorageAccess::Read { length, .. } => *length as u64,
+                StorageAccess::Write { .. } => 0,
+            })
+            .sum()
+    }
+
+    /// returns the total number of the written bytes
+    pub fn bytes_written(&self) -> u64 {
+        self.accesses
+            .borrow()
+            .iter()
+            .map(|access| match access {
+                StorageAccess::Read { .. } => 0,
+                StorageAccess::Write { data, .. } => data.len() as u64,
+            })
+            .sum()
+    }
+
+    /// clears the trace, for example before the call under test
+    pub fn clear(&self) {
+        self.accesses.borrow_mut().clear();
+    }
+
+    /// panics if the storage is read more than `max` times
+    #[track_caller]
+    pub fn assert_reads_at_most(&self, max: usize) {
+        let reads = self.reads();
+        assert!(reads <= max, "{} reads, expected at most {}", reads, max);
+    }
+
+    /// panics if the storage is written more than `max` times
+    #[track_caller]
+    pub fn assert_writes_at_most(&self, max: usize) {
+        let writes = self.writes();
+        assert!(writes <= max, "{} writes, expected at most {}", writes, max);
+    }
+
+    /// panics if the storage is written, for example by a query
+    #[track_caller]
+    pub fn assert_no_writes(&self) {
+        self.assert_writes_at_most(0);
+    }
+
+    fn record(&self, access: StorageAccess) {
+        self.accesses.borrow_mut().push(access);
+    }
+}
+
+/// The failures that are injected into the storage mock.
+/// The offsets are the offsets of the storage file, so they include the reserved area.
+/// The failed calls return `Error::HostError(code)` and don't charge any gas.
+#[derive(Debug, Default)]
+pub struct StorageFaults {
+    nth_writes: RefCell<Vec<(usize, i32)>>,
+    read_ranges: RefCell<Vec<(Range<u32>, i32)>>,
+    write_ranges: RefCell<Vec<(Range<u32>, i32)>>,
+}
+
+impl StorageFaults {
+    /// fails the `n`th write from now on with the given code.
+    /// `n = 1` fails the next write. The fault happens once.
+    pub fn fail_nth_write(&self, n: usize, code: i32) {
+        assert!(n > 0, "n starts from one");
+        self.nth_writes.borrow_mut().push((n, code));
+    }
+
+    /// fails all the reads that overlap the given range with the given code
+    pub fn fail_reads(&self, range: Range<u32>, code: i32) {
+        self.read_ranges.borrow_mut().push((range, code));
+    }
+
+    /// fails all the writes that overlap the given range with the given code
+    pub fn fail_writes(&self, range: Range<u32>, code: i32) {
+        self.write_ranges.borrow_mut().push((range, code));
+    }
+
+    /// removes all the faults
+    pub fn clear(&self) {
+        self.nth_writes.borrow_mut().clear();
+        self.read_ranges.borrow_mut().clear();
+        self.write_ranges.borrow_mut().clear();
+    }
+
+    fn check_read(&self, offset: u32, length: u32) -> Result<(), Error> {
+        check_range(&self.read_ranges.borrow(), offset, length)
+    }
+
+    fn check_write(&self, offset: u32, length: u32) -> Result<(), Error> {
+        let mut fault = None;
+        self.nth_writes.borrow_mut().retain_mut(|(n, code)| {
+            *n -= 1;
+            if *n == 0 {
+                fault.get_or_insert(*code);
+            }
+            *n > 0
+        });
+        if let Some(code) = fault {
+            return Err(Error::HostError(code));
+        }
+        check_range(&self.write_ranges.borrow(), offset, length)
+    }
+}
+
+fn check_range(ranges: &[(Range<u32>, i32)], offset: u32, length: u32) -> Result<(), Error> {
+    let end = offset.saturating_add(length);
+    match ranges
+        .iter()
+        .find(|(range, _)| offset < range.end && range.start < end)
+    {
+        Some((_, code)) => Err(Error::HostError(*code)),
+        None => Ok(()),
     }
 }
 
 /// mocks the storage for testing purpose.
+/// It records the accesses to the storage, see [`MockStorageAPI::trace`],
+/// and it can fail them on demand, see [`MockStorageAPI::faults`].
+///
+/// The clones share the same storage, so a clone can be kept for checking the storage
+/// after the storage is moved into a context.
+#[derive(Clone)]
 pub struct MockStorageAPI {
-    storage: RefCell<Vec<u8>>,
+    storage: Rc<RefCell<Vec<u8>>>,
+    gas_meter: Option<Rc<MockGasMeter>>,
+    trace: Rc<StorageTrace>,
+    faults: Rc<StorageFaults>,
 }
 
 impl MockStorageAPI {
     /// instantiates a new storage mock
     pub fn new(size: usize) -> Self {
-        let storage = RefCell::new(alloc::vec![0; size].to_vec());
-        Self { storage }
+        let storage = Rc::new(RefCell::new(alloc::vec![0; size]));
+        Self {
+            storage,
+            gas_meter: None,
+            trace: Rc::new(StorageTrace::default()),
+            faults: Rc::new(StorageFaults::default()),
+        }
+    }
+
+    /// instantiates a new storage mock that charges the gas meter for each read and write
+    pub fn with_gas_meter(size: usize, gas_meter: Rc<MockGasMeter>) -> Self {
+        Self {
+            gas_meter: Some(gas_meter),
+            ..Self::new(size)
+        }
+    }
+
+    /// writes the layout header, like the storage of an instantiated contract.
+    /// Without it, the storage has the legacy layout.
+    /// Writing the header doesn't charge gas and it isn't recorded in the trace.
+    pub fn with_layout(self) -> Self {
+        self.storage.borrow_mut()[..LAYOUT_HEADER_SIZE as usize].copy_from_slice(&layout_header());
+        self
+    }
+
+    /// returns the trace of the accesses to this storage.
+    /// The trace is shared, so it can be checked after the storage is moved into a context.
+    pub fn trace(&self) -> Rc<StorageTrace> {
+        self.trace.clone()
+    }
+
+    /// returns a copy of the storage file.
+    /// Taking a snapshot doesn't charge gas and it isn't recorded in the trace.
+    pub fn snapshot(&self) -> StorageSnapshot {
+        StorageSnapshot::new(self.storage.borrow().clone())
+    }
+
+    /// replaces the storage file with the snapshot
+    pub fn restore(&self, snapshot: &StorageSnapshot) {
+        *self.storage.borrow_mut() = snapshot.as_bytes().to_vec();
+    }
+
+    /// returns the faults that are injected into this storage.
+    /// Like the trace, they are shared and can be changed after the storage is moved.
+    pub fn faults(&self) -> Rc<StorageFaults> {
+        self.faults.clone()
+    }
+
+    /// charges the gas meter. A cost that overflows runs out of gas.
+    fn charge(&self, cost: impl Fn(&GasCosts) -> Option<u64>) -> Result<(), Error> {
+        match &self.gas_meter {
+            Some(meter) => match cost(meter.costs()) {
+                Some(cost) => meter.consume(cost),
+                None => meter.exhaust(),
+            },
+            None => Ok(()),
+        }
     }
 }
 
+/// mocks the cryptographic APIs for testing purpose by pure Rust implementations.
+pub struct MockCryptoAPI {}
+
+impl CryptoAPI for MockCryptoAPI {
+    fn blake2b_256(&self, data: &[u8]) -> Result<Hash, Error> {
+        use blake2::digest::consts::U32;
+        use blake2::Digest;
+        Ok(blake2::Blake2b::<U32>::digest(data).into())
+    }
+
+    fn sha256(&self, data: &[u8]) -> Result<Hash, Error> {
+        use sha2::Digest;
+        Ok(sha2::Sha256::digest(data).into())
+    }
+
+    fn verify_ed25519(
+        &self,
+        public_key: &[u8; ED25519_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; ED25519_SIGNATURE_SIZE],
+    ) -> Result<bool, Error> {
+        let public_key = match ed25519_dalek::VerifyingKey::from_bytes(public_key) {
+            Ok(pk) => pk,
+            Err(_) => return Ok(false),
+        };
+        let signature = ed25519_dalek::Signature::from_bytes(signature);
+        Ok(public_key.verify_strict(msg, &signature).is_ok())
+    }
+
+    fn verify_bls(
+        &self,
+        public_key: &[u8; BLS_PUBLIC_KEY_SIZE],
+        msg: &[u8],
+        signature: &[u8; BLS_SIGNATURE_SIZE],
+    ) -> Result<bool, Error> {
+        use bls12_381::hash_to_curve::HashToCurve;
+        use bls12_381::{pairing, G1Affine, G1Projective, G2Affine};
+
+        let public_key = G2Affine::from_compressed(public_key);
+        let signature = G1Affine::from_compressed(signature);
+        if public_key.is_none().into() || signature.is_none().into() {
+            return Ok(false);
+        }
+        let public_key = public_key.unwrap();
+        if public_key.is_identity().into() {
+            return Ok(false);
+        }
+        let hash = <G1Projective as HashToCurve<ExpandMsgSha256>>::hash_to_curve(msg, BLS_DST);
+
+        Ok(pairing(&signature.unwrap(), &G2Affine::generator())
+            == pairing(&G1Affine::from(hash), &public_key))
+    }
+}
+
+/// A handler that receives the CBOR encoded message and returns the CBOR encoded envelope.
+type MockHandler = Box<dyn Fn(&[u8]) -> Vec<u8>>;
+
 /// `MockBlockchain` mocks the blockchain for testing purpose.
-pub struct MockBlockchain {}
+pub struct MockBlockchain {
+    address: Address,
+    attached_amount: Amount,
+    balances: RefCell<BTreeMap<Address, Amount>>,
+    queries: BTreeMap<Address, MockHandler>,
+    processes: BTreeMap<Address, MockHandler>,
+    gas_meter: Rc<MockGasMeter>,
+    block_number: u64,
+}
 
 impl MockBlockchain {
-    /// instantiates a new blockchain mock
+    /// instantiates a new blockchain mock with unlimited gas
     pub fn new() -> Self {
-        Self {}
+        Self::with_gas_meter(Rc::new(MockGasMeter::new(u64::MAX, GasCosts::default())))
+    }
+
+    /// instantiates a new blockchain mock with the given gas meter
+    pub fn with_gas_meter(gas_meter: Rc<MockGasMeter>) -> Self {
+        Self {
+            address: Address::from([0; 21]),
+            attached_amount: Amount::ZERO,
+            balances: RefCell::new(BTreeMap::new()),
+            queries: BTreeMap::new(),
+            processes: BTreeMap::new(),
+            gas_meter,
+            block_number: 1,
+        }
+    }
+
+    /// returns the gas meter of the blockchain mock
+    pub fn gas_meter(&self) -> &MockGasMeter {
+        &self.gas_meter
+    }
+
+    /// returns the address of the contract under test
+    pub fn address(&self) -> &Address {
+        &self.address
     }
+
+    /// sets the address of the contract under test
+    pub fn set_address(&mut self, address: Address) {
+        self.address = address;
+    }
+
+    /// sets the number of the current block
+    pub fn set_block_number(&mut self, block_number: u64) {
+        self.block_number = block_number;
+    }
+
+    /// attaches coins to the current call and adds them to the contract's balance,
+    /// like the host does before calling the contract.
+    /// It fails if the balance overflows.
+    pub fn attach(&mut self, amount: Amount) -> Result<(), Error> {
+        let balance = self
+            .balance_of(&self.address)
+            .checked_add(amount)
+            .ok_or(Error::GenericError("overflowed"))?;
+        self.attached_amount = amount;
+        self.set_balance(self.address, balance);
+        Ok(())
+    }
+
+    /// sets the balance of the given address
+    pub fn set_balance(&self, addr: Address, amount: Amount) {
+        self.balances.borrow_mut().insert(addr, amount);
+    }
+
+    /// returns the balance of the given address
+    pub fn balance_of(&self, addr: &Address) -> Amount {
+        self.balances
+            .borrow()
+            .get(addr)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// mocks the query entry of the contract at the given address.
+    pub fn mock_query<D, R, E>(
+        &mut self,
+        addr: Address,
+        handler: impl Fn(D) -> Result<R, E> + 'static,
+    ) where
+        D: for<'b> Decode<'b>,
+        R: Encode,
+        E: Encode,
+    {
+        self.queries
+            .insert(addr, Box::new(move |msg| handle(&handler, msg)));
+    }
+
+    /// mocks the process entry of the contract at the given address.
+    pub fn mock_process<D, R, E>(
+        &mut self,
+        addr: Address,
+        handler: impl Fn(D) -> Result<R, E> + 'static,
+    ) where
+        D: for<'b> Decode<'b>,
+        R: Into<Response>,
+        E: Encode,
+    {
+        self.processes.insert(
+            addr,
+            Box::new(move |msg| handle(&|msg| handler(msg).map(Into::into), msg)),
+        );
+    }
+}
+
+fn handle<D, R, E>(handler: &dyn Fn(D) -> Result<R, E>, msg: &[u8]) -> Vec<u8>
+where
+    D: for<'b> Decode<'b>,
+    R: Encode,
+    E: Encode,
+{
+    let res = match minicbor::decode(msg) {
+        Ok(msg) => Envelope::from(handler(msg)),
+        Err(err) => Envelope::InvalidMessage(err.to_string()),
+    };
+    let mut buf = Vec::new();
+    minicbor::encode(res, &mut buf).expect("Encoding failed");
+    buf
 }
 
 impl Default for MockBlockchain {
@@ -56,13 +620,89 @@ impl Default for MockBlockchain {
 
 impl Blockchain for MockBlockchain {
     fn get_param(&self, _param_id: i32) -> Option<ParamType> {
-        unimplemented!()
+        None
+    }
+
+    fn query_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let handler = self
+            .queries
+            .get(addr)
+            .ok_or(Error::HostError(ERR_CONTRACT_NOT_FOUND))?;
+        Ok(handler(msg))
+    }
+
+    fn process_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let handler = self
+            .processes
+            .get(addr)
+            .ok_or(Error::HostError(ERR_CONTRACT_NOT_FOUND))?;
+        Ok(handler(msg))
+    }
+
+    fn attached_amount(&self) -> Result<Amount, Error> {
+        Ok(self.attached_amount)
+    }
+
+    fn balance(&self) -> Result<Amount, Error> {
+        Ok(self.balance_of(&self.address))
+    }
+
+    fn transfer(&self, to: &Address, amount: Amount) -> Result<(), Error> {
+        let balance = self
+            .balance_of(&self.address)
+            .checked_sub(amount)
+            .ok_or(Error::HostError(ERR_INSUFFICIENT_FUNDS))?;
+        if *to == self.address {
+            return Ok(());
+        }
+        // The receiver is checked first, so a failed transfer doesn't change any balance.
+        let to_balance = self
+            .balance_of(to)
+            .checked_add(amount)
+            .ok_or(Error::GenericError("overflowed"))?;
+        self.set_balance(self.address, balance);
+        self.set_balance(*to, to_balance);
+        Ok(())
+    }
+
+    fn gas_remaining(&self) -> u64 {
+        self.gas_meter.remaining()
+    }
+
+    fn consume_gas(&self, amount: u64) -> Result<(), Error> {
+        self.gas_meter.consume(amount)
+    }
+
+    fn block_number(&self) -> u64 {
+        self.block_number
+    }
+}
+
+impl MockStorageAPI {
+    fn read_storage(&self, offset: u32, length: u32) -> Result<Vec<u8>, Error> {
+        self.faults.check_read(offset, length)?;
+        self.charge(|costs| costs.read_cost(length))?;
+        let end = offset
+            .checked_add(length)
+            .filter(|end| *end as usize <= self.storage.borrow().len())
+            .ok_or(Error::GenericError("overflowed"))?;
+        let c = &self.storage.borrow()[offset as usize..end as usize];
+        Ok(c.into())
     }
 }
 
 impl StorageAPI for MockStorageAPI {
     fn write(&self, offset: u32, data: &[u8]) -> Result<(), Error> {
-        if offset as usize + data.len() > self.storage.borrow().len() {
+        self.trace.record(StorageAccess::Write {
+            offset,
+            data: data.to_vec(),
+        });
+        self.faults.check_write(offset, data.len() as u32)?;
+        self.charge(|costs| costs.write_cost(data.len() as u32))?;
+        let end = (offset as usize)
+            .checked_add(data.len())
+            .ok_or(Error::GenericError("overflowed"))?;
+        if end > self.storage.borrow().len() {
             return Err(Error::GenericError("overflowed"));
         }
         for (i, d) in data.iter().enumerate() {
@@ -72,10 +712,189 @@ impl StorageAPI for MockStorageAPI {
     }
 
     fn read(&self, offset: u32, length: u32) -> Result<Vec<u8>, Error> {
-        if (offset + length) as usize > self.storage.borrow().len() {
-            return Err(Error::GenericError("overflowed"));
-        }
-        let c = &self.storage.borrow()[offset as usize..(offset + length) as usize];
-        Ok(c.into())
+        let res = self.read_storage(offset, length);
+        self.trace.record(StorageAccess::Read {
+            offset,
+            length,
+            data: res.as_ref().map(Clone::clone).unwrap_or_default(),
+        });
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_accounting() {
+        let costs = GasCosts::default();
+        let ctx = mock_context(16);
+        let ctx = ctx.as_ref();
+
+        ctx.storage.write_u32(0, 1).unwrap();
+        ctx.storage.read_u64(0).unwrap();
+        let used = costs.write_cost(4).unwrap() + costs.read_cost(8).unwrap();
+        assert_eq!(ctx.blockchain.gas_remaining(), u64::MAX - used);
+
+        ctx.blockchain.consume_gas(10).unwrap();
+        assert_eq!(ctx.blockchain.gas_remaining(), u64::MAX - used - 10);
+    }
+
+    #[test]
+    fn test_params() {
+        let ctx = mock_context(0);
+        assert!(ctx.blockchain.get_param(0).is_none());
+    }
+
+    #[test]
+    fn test_out_of_gas() {
+        let costs = GasCosts {
+            read_base: 1,
+            read_per_byte: 1,
+            write_base: 10,
+            write_per_byte: 10,
+        };
+        let ctx = mock_context_with_gas(16, 100, costs);
+        let ctx_ref = ctx.as_ref();
+
+        ctx_ref.storage.write_u32(0, 1).unwrap(); // 50
+        assert_eq!(ctx_ref.blockchain.gas_remaining(), 50);
+        assert!(matches!(
+            ctx_ref.storage.write_u64(0, 1),
+            Err(Error::HostError(ERR_OUT_OF_GAS))
+        ));
+        assert_eq!(ctx_ref.blockchain.gas_remaining(), 0);
+        assert_eq!(ctx.blockchain.gas_meter().used(), 100);
+        assert!(ctx_ref.storage.read_u32(0).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let costs = GasCosts {
+            read_base: 1,
+            read_per_byte: u64::MAX,
+            write_base: u64::MAX,
+            write_per_byte: 1,
+        };
+        assert_eq!(costs.read_cost(2), None);
+        assert_eq!(costs.write_cost(1), None);
+        assert_eq!(costs.write_cost(0), Some(u64::MAX));
+
+        // A cost that overflows runs out of gas even with unlimited gas.
+        let ctx = mock_context_with_gas(16, u64::MAX, costs);
+        let ctx_ref = ctx.as_ref();
+        assert!(matches!(
+            ctx_ref.storage.read(0, 2),
+            Err(Error::HostError(ERR_OUT_OF_GAS))
+        ));
+        assert_eq!(ctx_ref.blockchain.gas_remaining(), 0);
+
+        // The end of the range overflows.
+        let api = MockStorageAPI::new(16);
+        assert!(matches!(
+            api.read(u32::MAX, 2),
+            Err(Error::GenericError("overflowed"))
+        ));
+        assert!(api.write(u32::MAX, &[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_trace() {
+        let (ctx, trace) = mock_context_with_trace(16);
+        let ctx = ctx.as_ref();
+
+        ctx.storage.write_u16(0, 0x0102).unwrap();
+        ctx.storage.read_u8(1).unwrap();
+        assert!(ctx.storage.read_u8(16).is_err());
+        assert_eq!(
+            trace.accesses(),
+            [
+                StorageAccess::Write {
+                    offset: RESERVED_SIZE,
+                    data: alloc::vec![1, 2]
+                },
+                StorageAccess::Read {
+                    offset: RESERVED_SIZE + 1,
+                    length: 1,
+                    data: alloc::vec![2]
+                },
+                StorageAccess::Read {
+                    offset: RESERVED_SIZE + 16,
+                    length: 1,
+                    data: alloc::vec![]
+                },
+            ]
+        );
+        assert_eq!(trace.reads(), 2);
+        assert_eq!(trace.writes(), 1);
+        assert_eq!(trace.bytes_read(), 2);
+        assert_eq!(trace.bytes_written(), 2);
+        trace.assert_reads_at_most(2);
+
+        trace.clear();
+        ctx.storage.read_u32(0).unwrap();
+        trace.assert_no_writes();
+        assert_eq!(trace.reads(), 1);
+    }
+
+    #[test]
+    fn test_faults() {
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 16);
+        let faults = api.faults();
+        let storage = Storage::new(Box::new(api));
+
+        faults.fail_nth_write(2, 10);
+        storage.write_u8(0, 1).unwrap();
+        assert!(matches!(storage.write_u8(1, 2), Err(Error::HostError(10))));
+        storage.write_u8(1, 2).unwrap();
+
+        faults.fail_reads(RESERVED_SIZE + 4..RESERVED_SIZE + 8, 11);
+        assert_eq!(storage.read_u32(0).unwrap(), 0x01020000);
+        assert!(matches!(storage.read_u32(2), Err(Error::HostError(11))));
+        assert!(matches!(storage.read_u8(7), Err(Error::HostError(11))));
+        assert_eq!(storage.read_u8(8).unwrap(), 0);
+
+        faults.fail_writes(RESERVED_SIZE..RESERVED_SIZE + 1, ERR_OUT_OF_GAS);
+        assert!(matches!(
+            storage.write_u16(0, 1),
+            Err(Error::HostError(ERR_OUT_OF_GAS))
+        ));
+        storage.write_u8(1, 3).unwrap();
+
+        faults.clear();
+        storage.write_u16(0, 1).unwrap();
+        assert_eq!(storage.read_u32(0).unwrap(), 0x00010000);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 16);
+        let storage = Storage::new(Box::new(api.clone()));
+
+        storage.write_u32(0, 1).unwrap();
+        let snapshot = api.snapshot();
+        storage.write_u32(4, 2).unwrap();
+        storage.write_u8(3, 3).unwrap();
+        assert_eq!(
+            snapshot.diff(&api.snapshot()),
+            [
+                RESERVED_SIZE + 3..RESERVED_SIZE + 4,
+                RESERVED_SIZE + 7..RESERVED_SIZE + 8
+            ]
+        );
+
+        api.restore(&snapshot);
+        assert_eq!(storage.read_u32(0).unwrap(), 1);
+        assert_eq!(storage.read_u32(4).unwrap(), 0);
+        assert_eq!(api.trace().reads(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 writes, expected at most 0")]
+    fn test_trace_assertion() {
+        let (ctx, trace) = mock_context_with_trace(16);
+        ctx.as_ref().storage.write_u8(0, 1).unwrap();
+        trace.assert_no_writes();
     }
 }
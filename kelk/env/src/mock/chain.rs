@@ -0,0 +1,851 @@
+//! A mocked chain that hosts several contracts
+//!
+//! Each contract on the chain has its own storage and balance. The contracts are
+//! native functions that receive the CBOR encoded message and return the CBOR encoded
+//! envelope, usually by wrapping the dispatchers with the functions of [`entry`](crate::entry):
+//!
+//! ```ignore
+//! let chain = MockChain::new();
+//! let token = chain.deploy(
+//!     MockContract::new()
+//!         .with_process(|ctx, msg| entry::process(&Token::dispatch_process, ctx, msg))
+//!         .with_query(|ctx, msg| entry::query(&Token::dispatch_query, ctx, msg)),
+//!     1024,
+//! );
+//! ```
+//!
+//! The chain routes the messages between the contracts with the rules of the host,
+//! see [`blockchain`](crate::blockchain). After a successful instantiate or process call,
+//! the events of the response are recorded and its sub-messages are sent in order,
+//! after the call has left the call stack.
+//! If the call or one of its sub-messages fails, the changes of the call are reverted.
+//! A failed sub-message fails the call with `ERR_SUB_MESSAGE_FAILED`.
+
+use super::{
+    mock_crypto, GasCosts, MockGasMeter, MockStorageAPI, StorageFaults, StorageSnapshot,
+    StorageTrace,
+};
+use crate::address::Address;
+use crate::blockchain::{decode_envelope, encode_msg, Amount, Blockchain, MAX_CALL_DEPTH};
+use crate::context::{Context, OwnedContext};
+use crate::envelope::TAG_OK;
+use crate::error::{
+    Error, ERR_CALL_DEPTH_EXCEEDED, ERR_CONTRACT_NOT_FOUND, ERR_INSUFFICIENT_FUNDS, ERR_READ_ONLY,
+    ERR_REENTRANCY, ERR_SUB_MESSAGE_FAILED,
+};
+use crate::params::ParamType;
+use crate::response::{Event, Response};
+use crate::storage::{Storage, RESERVED_SIZE};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use minicbor::{Decode, Decoder, Encode};
+
+/// An entry point of a mocked contract.
+/// It receives the CBOR encoded message and returns the CBOR encoded envelope.
+pub type EntryFn = Box<dyn Fn(Context, &[u8]) -> Vec<u8>>;
+
+/// The entry points of a contract on the mocked chain
+#[derive(Default)]
+pub struct MockContract {
+    instantiate: Option<EntryFn>,
+    process: Option<EntryFn>,
+    query: Option<EntryFn>,
+}
+
+impl MockContract {
+    /// creates a contract without any entry point
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the instantiate entry point
+    pub fn with_instantiate(mut self, f: impl Fn(Context, &[u8]) -> Vec<u8> + 'static) -> Self {
+        self.instantiate = Some(Box::new(f));
+        self
+    }
+
+    /// sets the process entry point
+    pub fn with_process(mut self, f: impl Fn(Context, &[u8]) -> Vec<u8> + 'static) -> Self {
+        self.process = Some(Box::new(f));
+        self
+    }
+
+    /// sets the query entry point
+    pub fn with_query(mut self, f: impl Fn(Context, &[u8]) -> Vec<u8> + 'static) -> Self {
+        self.query = Some(Box::new(f));
+        self
+    }
+
+    fn entry(&self, entry: Entry) -> Option<&EntryFn> {
+        match entry {
+            Entry::Instantiate => self.instantiate.as_ref(),
+            Entry::Process => self.process.as_ref(),
+            Entry::Query => self.query.as_ref(),
+        }
+    }
+}
+
+/// The entry points that the chain can call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// The instantiate entry point
+    Instantiate,
+    /// The process entry point
+    Process,
+    /// The query entry point. Queries are read-only.
+    Query,
+}
+
+/// An event that is recorded by the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainEvent {
+    /// The number of the block that the event is emitted in
+    pub block_number: u64,
+    /// The address of the contract that emitted the event
+    pub contract: Address,
+    /// The event
+    pub event: Event,
+}
+
+struct Instance {
+    code: Rc<MockContract>,
+    storage: MockStorageAPI,
+}
+
+struct Frame {
+    contract: Address,
+    read_only: bool,
+}
+
+/// The state of the chain that can be reverted
+struct Snapshot {
+    storages: Vec<(Address, StorageSnapshot)>,
+    balances: BTreeMap<Address, Amount>,
+    events: usize,
+}
+
+struct ChainState {
+    contracts: RefCell<BTreeMap<Address, Instance>>,
+    balances: RefCell<BTreeMap<Address, Amount>>,
+    events: RefCell<Vec<ChainEvent>>,
+    stack: RefCell<Vec<Frame>>,
+    block_number: Cell<u64>,
+    next_id: Cell<u32>,
+    gas_meter: Rc<MockGasMeter>,
+}
+
+/// `MockChain` hosts several contracts for testing the flows that span them.
+pub struct MockChain {
+    state: Rc<ChainState>,
+}
+
+impl MockChain {
+    /// creates an empty chain with unlimited gas, at block one
+    pub fn new() -> Self {
+        Self::with_gas(u64::MAX, GasCosts::default())
+    }
+
+    /// creates an empty chain with the given gas limit and cost model.
+    /// All the calls share the same gas meter.
+    pub fn with_gas(gas_limit: u64, costs: GasCosts) -> Self {
+        Self {
+            state: Rc::new(ChainState {
+                contracts: RefCell::new(BTreeMap::new()),
+                balances: RefCell::new(BTreeMap::new()),
+                events: RefCell::new(Vec::new()),
+                stack: RefCell::new(Vec::new()),
+                block_number: Cell::new(1),
+                next_id: Cell::new(1),
+                gas_meter: Rc::new(MockGasMeter::new(gas_limit, costs)),
+            }),
+        }
+    }
+
+    /// deploys the contract with `storage_size` bytes of storage and returns its address
+    pub fn deploy(&self, code: MockContract, storage_size: usize) -> Address {
+        let id = self.state.next_id.get();
+        self.state.next_id.set(id + 1);
+        let mut addr = [0; 21];
+        addr[0] = 1;
+        addr[17..].copy_from_slice(&id.to_be_bytes());
+        let addr = Address::from(addr);
+        self.deploy_at(addr, code, storage_size);
+        addr
+    }
+
+    /// deploys the contract at the given address. An existing contract is replaced.
+    /// Like the mocked context, the storage has the current layout.
+    pub fn deploy_at(&self, addr: Address, code: MockContract, storage_size: usize) {
+        let storage = MockStorageAPI::with_gas_meter(
+            RESERVED_SIZE as usize + storage_size,
+            self.state.gas_meter.clone(),
+        )
+        .with_layout();
+        self.state.contracts.borrow_mut().insert(
+            addr,
+            Instance {
+                code: Rc::new(code),
+                storage,
+            },
+        );
+    }
+
+    /// returns the storage of the contract, for checking its state
+    pub fn storage(&self, contract: &Address) -> Option<Storage> {
+        let contracts = self.state.contracts.borrow();
+        let storage = contracts.get(contract)?.storage.clone();
+        Some(Storage::new(Box::new(storage)))
+    }
+
+    /// returns a snapshot of the storage of the contract
+    pub fn storage_snapshot(&self, contract: &Address) -> Option<StorageSnapshot> {
+        let contracts = self.state.contracts.borrow();
+        Some(contracts.get(contract)?.storage.snapshot())
+    }
+
+    /// returns the trace of the accesses to the storage of the contract
+    pub fn storage_trace(&self, contract: &Address) -> Option<Rc<StorageTrace>> {
+        let contracts = self.state.contracts.borrow();
+        Some(contracts.get(contract)?.storage.trace())
+    }
+
+    /// returns the faults that are injected into the storage of the contract
+    pub fn storage_faults(&self, contract: &Address) -> Option<Rc<StorageFaults>> {
+        let contracts = self.state.contracts.borrow();
+        Some(contracts.get(contract)?.storage.faults())
+    }
+
+    /// returns the gas meter of the chain
+    pub fn gas_meter(&self) -> &MockGasMeter {
+        &self.state.gas_meter
+    }
+
+    /// sets the balance of the account
+    pub fn set_balance(&self, addr: Address, amount: Amount) {
+        self.state.balances.borrow_mut().insert(addr, amount);
+    }
+
+    /// returns the balance of the account
+    pub fn balance_of(&self, addr: &Address) -> Amount {
+        self.state.balance_of(addr)
+    }
+
+    /// returns the number of the current block
+    pub fn block_number(&self) -> u64 {
+        self.state.block_number.get()
+    }
+
+    /// advances the chain by the given number of blocks
+    pub fn advance_blocks(&self, blocks: u64) {
+        let block_number = self.state.block_number.get();
+        self.state.block_number.set(block_number + blocks);
+    }
+
+    /// returns the events that are recorded so far
+    pub fn events(&self) -> Vec<ChainEvent> {
+        self.state.events.borrow().clone()
+    }
+
+    /// returns the recorded events and clears them
+    pub fn take_events(&self) -> Vec<ChainEvent> {
+        core::mem::take(&mut *self.state.events.borrow_mut())
+    }
+
+    /// calls the entry point of the contract with the CBOR encoded message
+    /// and returns the CBOR encoded envelope.
+    /// The `amount` is transferred from the `sender` to the contract before the call.
+    pub fn execute(
+        &self,
+        entry: Entry,
+        sender: &Address,
+        contract: &Address,
+        msg: &[u8],
+        amount: Amount,
+    ) -> Result<Vec<u8>, Error> {
+        call(&self.state, entry, sender, contract, msg, amount)
+    }
+
+    /// instantiates the contract.
+    /// The outer result fails if the chain can't execute the call,
+    /// the inner result is the result of the contract.
+    pub fn instantiate<M, E>(
+        &self,
+        sender: &Address,
+        contract: &Address,
+        msg: &M,
+        amount: Amount,
+    ) -> Result<Result<Response, E>, Error>
+    where
+        M: Encode,
+        E: for<'b> Decode<'b>,
+    {
+        let data = self.execute(
+            Entry::Instantiate,
+            sender,
+            contract,
+            &encode_msg(msg)?,
+            amount,
+        )?;
+        decode_envelope(&data)
+    }
+
+    /// calls the process entry of the contract.
+    /// The outer result fails if the chain can't execute the call,
+    /// the inner result is the result of the contract.
+    pub fn process<M, E>(
+        &self,
+        sender: &Address,
+        contract: &Address,
+        msg: &M,
+        amount: Amount,
+    ) -> Result<Result<Response, E>, Error>
+    where
+        M: Encode,
+        E: for<'b> Decode<'b>,
+    {
+        let data = self.execute(Entry::Process, sender, contract, &encode_msg(msg)?, amount)?;
+        decode_envelope(&data)
+    }
+
+    /// queries the contract.
+    /// The outer result fails if the chain can't execute the call,
+    /// the inner result is the result of the contract.
+    pub fn query<M, R, E>(&self, contract: &Address, msg: &M) -> Result<Result<R, E>, Error>
+    where
+        M: Encode,
+        R: for<'b> Decode<'b>,
+        E: for<'b> Decode<'b>,
+    {
+        let sender = Address::from([0; 21]);
+        let data = self.execute(
+            Entry::Query,
+            &sender,
+            contract,
+            &encode_msg(msg)?,
+            Amount::ZERO,
+        )?;
+        decode_envelope(&data)
+    }
+}
+
+impl Default for MockChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChainState {
+    fn balance_of(&self, addr: &Address) -> Amount {
+        self.balances
+            .borrow()
+            .get(addr)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn transfer(&self, from: &Address, to: &Address, amount: Amount) -> Result<(), Error> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        let balance = self
+            .balance_of(from)
+            .checked_sub(amount)
+            .ok_or(Error::HostError(ERR_INSUFFICIENT_FUNDS))?;
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = self
+            .balance_of(to)
+            .checked_add(amount)
+            .ok_or(Error::GenericError("overflowed"))?;
+        let mut balances = self.balances.borrow_mut();
+        balances.insert(*from, balance);
+        balances.insert(*to, to_balance);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            storages: self
+                .contracts
+                .borrow()
+                .iter()
+                .map(|(addr, instance)| (*addr, instance.storage.snapshot()))
+                .collect(),
+            balances: self.balances.borrow().clone(),
+            events: self.events.borrow().len(),
+        }
+    }
+
+    fn restore(&self, snapshot: Snapshot) {
+        let contracts = self.contracts.borrow();
+        for (addr, storage) in snapshot.storages {
+            if let Some(instance) = contracts.get(&addr) {
+                instance.storage.restore(&storage);
+            }
+        }
+        *self.balances.borrow_mut() = snapshot.balances;
+        self.events.borrow_mut().truncate(snapshot.events);
+    }
+
+    /// checks the rules of nested calls and pushes the call into the stack
+    fn enter(&self, contract: &Address, entry: Entry) -> Result<(), Error> {
+        let mut stack = self.stack.borrow_mut();
+        if stack.len() >= MAX_CALL_DEPTH as usize {
+            return Err(Error::HostError(ERR_CALL_DEPTH_EXCEEDED));
+        }
+        if entry != Entry::Query {
+            if stack.iter().any(|frame| frame.read_only) {
+                return Err(Error::HostError(ERR_READ_ONLY));
+            }
+            if stack.iter().any(|frame| frame.contract == *contract) {
+                return Err(Error::HostError(ERR_REENTRANCY));
+            }
+        }
+        let read_only = entry == Entry::Query;
+        stack.push(Frame {
+            contract: *contract,
+            read_only,
+        });
+        Ok(())
+    }
+}
+
+fn call(
+    state: &Rc<ChainState>,
+    entry: Entry,
+    sender: &Address,
+    contract: &Address,
+    msg: &[u8],
+    amount: Amount,
+) -> Result<Vec<u8>, Error> {
+    let (code, storage) = {
+        let contracts = state.contracts.borrow();
+        let instance = contracts
+            .get(contract)
+            .ok_or(Error::HostError(ERR_CONTRACT_NOT_FOUND))?;
+        (instance.code.clone(), instance.storage.clone())
+    };
+    let entry_fn = code
+        .entry(entry)
+        .ok_or(Error::HostError(ERR_CONTRACT_NOT_FOUND))?;
+
+    state.enter(contract, entry)?;
+    let snapshot = state.snapshot();
+    let res = execute(state, entry_fn, storage, sender, contract, msg, amount);
+    state.stack.borrow_mut().pop();
+    // The sub-messages are sent when the call is not in the stack anymore.
+    let res = res.and_then(|data| send_messages(state, entry, contract, data));
+
+    match res {
+        Ok(data) if is_ok(&data) => Ok(data),
+        res => {
+            state.restore(snapshot);
+            res
+        }
+    }
+}
+
+fn execute(
+    state: &Rc<ChainState>,
+    entry_fn: &EntryFn,
+    storage: MockStorageAPI,
+    sender: &Address,
+    contract: &Address,
+    msg: &[u8],
+    amount: Amount,
+) -> Result<Vec<u8>, Error> {
+    state.transfer(sender, contract, amount)?;
+
+    let ctx = OwnedContext {
+        blockchain: ChainBlockchain {
+            state: state.clone(),
+            address: *contract,
+            attached_amount: amount,
+        },
+        storage: Storage::new(Box::new(storage)),
+        crypto: mock_crypto(),
+    };
+    Ok(entry_fn(ctx.as_ref(), msg))
+}
+
+/// records the events of a successful response and sends its sub-messages in order.
+/// If a sub-message fails, it returns `ERR_SUB_MESSAGE_FAILED`.
+fn send_messages(
+    state: &Rc<ChainState>,
+    entry: Entry,
+    contract: &Address,
+    data: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    if entry == Entry::Query {
+        return Ok(data);
+    }
+
+    if let Some(rsp) = ok_response(&data) {
+        let block_number = state.block_number.get();
+        state
+            .events
+            .borrow_mut()
+            .extend(rsp.events.into_iter().map(|event| ChainEvent {
+                block_number,
+                contract: *contract,
+                event,
+            }));
+        for sub_msg in rsp.messages {
+            let res = call(
+                state,
+                Entry::Process,
+                contract,
+                &sub_msg.contract,
+                &sub_msg.msg,
+                Amount::ZERO,
+            );
+            if !res.is_ok_and(|data| is_ok(&data)) {
+                return Err(Error::HostError(ERR_SUB_MESSAGE_FAILED));
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// checks if the envelope is `Ok`
+fn is_ok(data: &[u8]) -> bool {
+    let mut d = Decoder::new(data);
+    d.array().is_ok() && d.u32().is_ok_and(|tag| tag == TAG_OK)
+}
+
+/// returns the response if the envelope is `Ok`
+fn ok_response(data: &[u8]) -> Option<Response> {
+    let mut d = Decoder::new(data);
+    d.array().ok()?;
+    if d.u32().ok()? != TAG_OK {
+        return None;
+    }
+    d.decode().ok()
+}
+
+/// The blockchain APIs of a contract on the chain
+struct ChainBlockchain {
+    state: Rc<ChainState>,
+    address: Address,
+    attached_amount: Amount,
+}
+
+impl Blockchain for ChainBlockchain {
+    fn get_param(&self, _param_id: i32) -> Option<ParamType> {
+        None
+    }
+
+    fn query_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        call(
+            &self.state,
+            Entry::Query,
+            &self.address,
+            addr,
+            msg,
+            Amount::ZERO,
+        )
+    }
+
+    fn process_contract(&self, addr: &Address, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        call(
+            &self.state,
+            Entry::Process,
+            &self.address,
+            addr,
+            msg,
+            Amount::ZERO,
+        )
+    }
+
+    fn attached_amount(&self) -> Result<Amount, Error> {
+        Ok(self.attached_amount)
+    }
+
+    fn balance(&self) -> Result<Amount, Error> {
+        Ok(self.state.balance_of(&self.address))
+    }
+
+    fn transfer(&self, to: &Address, amount: Amount) -> Result<(), Error> {
+        if self
+            .state
+            .stack
+            .borrow()
+            .iter()
+            .any(|frame| frame.read_only)
+        {
+            return Err(Error::HostError(ERR_READ_ONLY));
+        }
+        self.state.transfer(&self.address, to, amount)
+    }
+
+    fn gas_remaining(&self) -> u64 {
+        self.state.gas_meter.remaining()
+    }
+
+    fn consume_gas(&self, amount: u64) -> Result<(), Error> {
+        self.state.gas_meter.consume(amount)
+    }
+
+    fn block_number(&self) -> u64 {
+        self.state.block_number.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry;
+    use crate::response::SubMessage;
+    use alloc::string::{String, ToString};
+
+    /// A token that mints the amount in the process message
+    /// and returns its supply for queries.
+    fn token() -> MockContract {
+        MockContract::new()
+            .with_process(|ctx, msg| {
+                entry::process(
+                    &|ctx: Context, amount: u64| -> Result<Response, u32> {
+                        if amount == 0 {
+                            return Err(1);
+                        }
+                        let supply = ctx.storage.read_u64(0).unwrap();
+                        ctx.storage.write_u64(0, supply + amount).unwrap();
+                        Ok(Response::new().add_event(Event::new("mint")))
+                    },
+                    ctx,
+                    msg,
+                )
+            })
+            .with_query(|ctx, msg| {
+                entry::query(
+                    &|ctx: Context, _: ()| -> Result<u64, u32> {
+                        Ok(ctx.storage.read_u64(0).unwrap())
+                    },
+                    ctx,
+                    msg,
+                )
+            })
+    }
+
+    /// A DEX that counts the swaps, keeps the attached coins
+    /// and asks the token to mint the swapped amount.
+    /// Its error type is not the same as the token's.
+    fn dex(token: Address) -> MockContract {
+        MockContract::new().with_process(move |ctx, msg| {
+            entry::process(
+                &|ctx: Context, amount: u64| -> Result<Response, String> {
+                    let swaps = ctx.storage.read_u64(0).unwrap();
+                    ctx.storage.write_u64(0, swaps + 1).unwrap();
+                    let supply: u64 = ctx
+                        .blockchain
+                        .query::<_, _, u32>(&token, &())
+                        .unwrap()
+                        .unwrap();
+                    Ok(Response::new()
+                        .add_event(Event::new("swap").add_attribute("supply", supply.to_string()))
+                        .add_message(SubMessage::new(token, &amount).unwrap()))
+                },
+                ctx,
+                msg,
+            )
+        })
+    }
+
+    #[test]
+    fn test_flow() {
+        let chain = MockChain::new();
+        let user = Address::from([9; 21]);
+        chain.set_balance(user, Amount::new(100));
+        let token = chain.deploy(token(), 8);
+        let dex = chain.deploy(dex(token), 8);
+        assert_ne!(token, dex);
+
+        chain.advance_blocks(2);
+        chain
+            .process::<_, String>(&user, &dex, &5u64, Amount::new(30))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(chain.query::<_, u64, u32>(&token, &()).unwrap(), Ok(5));
+        assert_eq!(chain.storage(&dex).unwrap().read_u64(0).unwrap(), 1);
+        assert_eq!(chain.balance_of(&user), Amount::new(70));
+        assert_eq!(chain.balance_of(&dex), Amount::new(30));
+
+        let events = chain.take_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].contract, dex);
+        assert_eq!(events[0].block_number, 3);
+        assert_eq!(events[0].event.attributes[0].value, "0");
+        assert_eq!(events[1].contract, token);
+        assert_eq!(events[1].event.kind, "mint");
+        assert!(chain.events().is_empty());
+    }
+
+    #[test]
+    fn test_revert() {
+        let chain = MockChain::new();
+        let user = Address::from([9; 21]);
+        chain.set_balance(user, Amount::new(100));
+        let token = chain.deploy(token(), 8);
+        let dex = chain.deploy(dex(token), 8);
+
+        // The token refuses to mint zero, so the whole swap is reverted.
+        let snapshot = chain.storage_snapshot(&dex).unwrap();
+        assert!(matches!(
+            chain.process::<_, String>(&user, &dex, &0u64, Amount::new(30)),
+            Err(Error::HostError(ERR_SUB_MESSAGE_FAILED))
+        ));
+        assert_eq!(chain.storage_snapshot(&dex).unwrap().diff(&snapshot), []);
+        assert_eq!(chain.balance_of(&user), Amount::new(100));
+        assert!(chain.events().is_empty());
+
+        assert!(matches!(
+            chain.process::<_, String>(&user, &dex, &1u64, Amount::new(200)),
+            Err(Error::HostError(ERR_INSUFFICIENT_FUNDS))
+        ));
+    }
+
+    /// The caller asks the token to mint with a sub-message,
+    /// and the token notifies the caller with another one.
+    #[test]
+    fn test_callback() {
+        let chain = MockChain::new();
+        let user = Address::from([9; 21]);
+        let caller = Address::from([1; 21]);
+        let token = Address::from([2; 21]);
+        chain.deploy_at(
+            token,
+            MockContract::new().with_process(move |ctx, msg| {
+                entry::process(
+                    &|_: Context, amount: u64| -> Result<Response, u32> {
+                        // Notifies the caller about the minted amount
+                        Ok(Response::new().add_message(SubMessage::new(caller, &amount).unwrap()))
+                    },
+                    ctx,
+                    msg,
+                )
+            }),
+            8,
+        );
+        chain.deploy_at(
+            caller,
+            MockContract::new().with_process(move |ctx, msg| {
+                entry::process(
+                    &|ctx: Context, amount: u64| -> Result<Response, u32> {
+                        if amount == 0 {
+                            // The callback of the token
+                            let calls = ctx.storage.read_u64(0).unwrap();
+                            ctx.storage.write_u64(0, calls + 1).unwrap();
+                            return Ok(Response::new());
+                        }
+                        Ok(Response::new().add_message(SubMessage::new(token, &0u64).unwrap()))
+                    },
+                    ctx,
+                    msg,
+                )
+            }),
+            8,
+        );
+
+        chain
+            .process::<_, u32>(&user, &caller, &5u64, Amount::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chain.storage(&caller).unwrap().read_u64(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_self_transfer() {
+        let chain = MockChain::new();
+        let user = Address::from([9; 21]);
+        let this = Address::from([1; 21]);
+        chain.set_balance(this, Amount::new(10));
+        chain.deploy_at(
+            this,
+            MockContract::new().with_process(move |ctx, msg| {
+                entry::process(
+                    &|ctx: Context, amount: Amount| -> Result<(), i32> {
+                        assert!(ctx.blockchain.get_param(0).is_none());
+                        ctx.blockchain
+                            .transfer(&this, amount)
+                            .map_err(|err| match err {
+                                Error::HostError(code) => code,
+                                _ => 0,
+                            })
+                    },
+                    ctx,
+                    msg,
+                )
+            }),
+            8,
+        );
+
+        chain
+            .process::<_, i32>(&user, &this, &Amount::new(10), Amount::ZERO)
+            .unwrap()
+            .unwrap();
+        assert_eq!(chain.balance_of(&this), Amount::new(10));
+        assert_eq!(
+            chain
+                .process::<_, i32>(&user, &this, &Amount::new(11), Amount::ZERO)
+                .unwrap(),
+            Err(ERR_INSUFFICIENT_FUNDS)
+        );
+        assert_eq!(chain.balance_of(&this), Amount::new(10));
+    }
+
+    #[test]
+    fn test_rules() {
+        let chain = MockChain::new();
+        let user = Address::from([9; 21]);
+        let this = Address::from([1; 21]);
+        chain.deploy_at(
+            this,
+            MockContract::new()
+                .with_process(move |ctx, msg| {
+                    entry::process(
+                        &|ctx: Context, _: ()| -> Result<(), i32> {
+                            match ctx.blockchain.process::<_, i32>(&this, &()) {
+                                Err(Error::HostError(code)) => Err(code),
+                                _ => Ok(()),
+                            }
+                        },
+                        ctx,
+                        msg,
+                    )
+                })
+                .with_query(move |ctx, msg| {
+                    entry::query(
+                        &|ctx: Context, _: ()| -> Result<(), i32> {
+                            match ctx.blockchain.process::<_, i32>(&this, &()) {
+                                Err(Error::HostError(code)) => Err(code),
+                                _ => Ok(()),
+                            }
+                        },
+                        ctx,
+                        msg,
+                    )
+                }),
+            8,
+        );
+
+        assert_eq!(
+            chain
+                .process::<_, i32>(&user, &this, &(), Amount::ZERO)
+                .unwrap(),
+            Err(ERR_REENTRANCY)
+        );
+        assert_eq!(
+            chain.query::<_, (), i32>(&this, &()).unwrap(),
+            Err(ERR_READ_ONLY)
+        );
+        assert!(matches!(
+            chain.query::<_, (), i32>(&user, &()),
+            Err(Error::HostError(ERR_CONTRACT_NOT_FOUND))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! `expand_message_xmd` with SHA-256 for hashing the messages to the BLS curve
+//!
+//! bls12_381 provides the expander only for the older `digest` traits, so it is implemented
+//! here over the same `sha2` that ed25519-dalek uses. See [RFC 9380, section 5.3.1][xmd].
+//!
+//! [xmd]: https://www.rfc-editor.org/rfc/rfc9380#section-5.3.1
+
+use alloc::vec;
+use alloc::vec::Vec;
+use bls12_381::hash_to_curve::{ExpandMessageState, InitExpandMessage};
+use sha2::{Digest, Sha256};
+
+/// The output size of SHA-256
+const HASH_SIZE: usize = 32;
+/// The input block size of SHA-256
+const BLOCK_SIZE: usize = 64;
+
+/// The `expand_message_xmd` expander with SHA-256
+pub(crate) struct ExpandMsgSha256 {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl<'x> InitExpandMessage<'x> for ExpandMsgSha256 {
+    type Expander = Self;
+
+    fn init_expand(message: &[u8], dst: &'x [u8], len_in_bytes: usize) -> Self {
+        // The domain separation tag of Kelk is short, so it is never hashed.
+        assert!(dst.len() <= 255, "domain separation tag is too long");
+        let ell = len_in_bytes.div_ceil(HASH_SIZE);
+        assert!(ell <= 255, "too many bytes requested");
+
+        let b_0 = Sha256::new()
+            .chain_update([0; BLOCK_SIZE])
+            .chain_update(message)
+            .chain_update((len_in_bytes as u16).to_be_bytes())
+            .chain_update([0])
+            .chain_update(dst)
+            .chain_update([dst.len() as u8])
+            .finalize();
+
+        let mut data = vec![0; ell * HASH_SIZE];
+        let mut b_i = [0; HASH_SIZE];
+        for (i, chunk) in data.chunks_mut(HASH_SIZE).enumerate() {
+            for (b, b0) in b_i.iter_mut().zip(b_0.iter()) {
+                *b ^= b0;
+            }
+            b_i = Sha256::new()
+                .chain_update(b_i)
+                .chain_update([i as u8 + 1])
+                .chain_update(dst)
+                .chain_update([dst.len() as u8])
+                .finalize()
+                .into();
+            chunk.copy_from_slice(&b_i);
+        }
+        data.truncate(len_in_bytes);
+        Self { data, offset: 0 }
+    }
+}
+
+impl ExpandMessageState<'_> for ExpandMsgSha256 {
+    fn read_into(&mut self, output: &mut [u8]) -> usize {
+        let len = self.remain().min(output.len());
+        output[..len].copy_from_slice(&self.data[self.offset..self.offset + len]);
+        self.offset += len;
+        len
+    }
+
+    fn remain(&self) -> usize {
+        self.data.len() - self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(msg: &[u8], len: usize) -> Vec<u8> {
+        let mut out = vec![0; len];
+        let mut expander =
+            ExpandMsgSha256::init_expand(msg, b"QUUX-V01-CS02-with-expander-SHA256-128", len);
+        assert_eq!(expander.read_into(&mut out), len);
+        assert_eq!(expander.remain(), 0);
+        out
+    }
+
+    #[test]
+    fn test_vectors() {
+        // https://www.rfc-editor.org/rfc/rfc9380#appendix-K.1
+        assert_eq!(
+            expand(b"", 0x20),
+            [
+                0x68, 0xa9, 0x85, 0xb8, 0x7e, 0xb6, 0xb4, 0x69, 0x52, 0x12, 0x89, 0x11, 0xf2, 0xa4,
+                0x41, 0x2b, 0xbc, 0x30, 0x2a, 0x9d, 0x75, 0x96, 0x67, 0xf8, 0x7f, 0x7a, 0x21, 0xd8,
+                0x03, 0xf0, 0x72, 0x35
+            ]
+        );
+        assert_eq!(
+            expand(b"abc", 0x20),
+            [
+                0xd8, 0xcc, 0xab, 0x23, 0xb5, 0x98, 0x5c, 0xce, 0xa8, 0x65, 0xc6, 0xc9, 0x7b, 0x6e,
+                0x5b, 0x83, 0x50, 0xe7, 0x94, 0xe6, 0x03, 0xb4, 0xb9, 0x79, 0x02, 0xf5, 0x3a, 0x8a,
+                0x0d, 0x60, 0x56, 0x15
+            ]
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Snapshots of the mocked storage
+//!
+//! A snapshot is a copy of the whole storage file, including the reserved area.
+//! Snapshots can be compared byte by byte and saved as fixtures, so a test can prove that
+//! the same sequence of messages produces the same state, for example after a migration:
+//!
+//! ```ignore
+//! let expected = StorageSnapshot::from_hex(include_str!("fixtures/state.hex"))?;
+//! assert_eq!(expected.diff(&storage.snapshot()), []);
+//! ```
+//!
+//! The hex format has 32 bytes on each line, so the changes in a fixture are readable
+//! in the version control.
+
+use crate::error::Error;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+/// The number of bytes on each line of the hex format
+const HEX_LINE_SIZE: usize = 32;
+
+/// A copy of the storage file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageSnapshot {
+    data: Vec<u8>,
+}
+
+impl StorageSnapshot {
+    /// creates a snapshot from the content of the storage file
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    /// returns the content of the storage file
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// returns the size of the storage file
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// returns true if the storage file is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// returns the ranges of the bytes that are different in the other snapshot.
+    /// If the sizes are different, the extra bytes are reported as changed.
+    pub fn diff(&self, other: &StorageSnapshot) -> Vec<Range<u32>> {
+        let len = self.data.len().max(other.data.len());
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for i in 0..len {
+            if self.data.get(i) == other.data.get(i) {
+                continue;
+            }
+            let i = i as u32;
+            match ranges.last_mut() {
+                Some(range) if range.end == i => range.end = i + 1,
+                _ => ranges.push(i..i + 1),
+            }
+        }
+        ranges
+    }
+
+    /// encodes the snapshot as hex, with `HEX_LINE_SIZE` bytes on each line
+    pub fn to_hex(&self) -> String {
+        let mut hex = String::with_capacity(self.data.len() * 2 + self.data.len() / HEX_LINE_SIZE);
+        for line in self.data.chunks(HEX_LINE_SIZE) {
+            for b in line {
+                write!(hex, "{:02x}", b).expect("writing into a string");
+            }
+            hex.push('\n');
+        }
+        hex
+    }
+
+    /// decodes the snapshot from hex. The whitespaces are ignored.
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let digits: Vec<u8> = hex
+            .bytes()
+            .filter(|c| !c.is_ascii_whitespace())
+            .map(|c| match c {
+                b'0'..=b'9' => Ok(c - b'0'),
+                b'a'..=b'f' => Ok(c - b'a' + 10),
+                b'A'..=b'F' => Ok(c - b'A' + 10),
+                _ => Err(Error::GenericError("invalid hex digit")),
+            })
+            .collect::<Result<_, _>>()?;
+        if !digits.len().is_multiple_of(2) {
+            return Err(Error::GenericError("odd number of hex digits"));
+        }
+        Ok(Self::new(
+            digits.chunks(2).map(|d| (d[0] << 4) | d[1]).collect(),
+        ))
+    }
+}
+
+#[cfg(feature = "std")]
+impl StorageSnapshot {
+    /// saves the snapshot as a hex fixture file
+    pub fn save_hex<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, self.to_hex())
+    }
+
+    /// loads the snapshot from a hex fixture file
+    pub fn load_hex<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let hex = std::fs::read_to_string(path)?;
+        Self::from_hex(&hex).map_err(|err| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, alloc::format!("{}", err))
+        })
+    }
+
+    /// saves the snapshot as a binary fixture file
+    pub fn save_binary<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        std::fs::write(path, &self.data)
+    }
+
+    /// loads the snapshot from a binary fixture file
+    pub fn load_binary<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self::new(std::fs::read(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_diff() {
+        let a = StorageSnapshot::new(vec![0, 1, 2, 3, 4, 5]);
+        let b = StorageSnapshot::new(vec![0, 9, 9, 3, 4, 9, 6]);
+
+        assert_eq!(a.diff(&a), []);
+        assert_eq!(a.diff(&b), [1..3, 5..7]);
+        assert_eq!(b.diff(&a), [1..3, 5..7]);
+    }
+
+    #[test]
+    fn test_hex() {
+        let snapshot = StorageSnapshot::new((0..40).collect());
+        let hex = snapshot.to_hex();
+        assert_eq!(hex.lines().count(), 2);
+        assert!(hex.starts_with("000102"));
+        assert_eq!(StorageSnapshot::from_hex(&hex).unwrap(), snapshot);
+        assert_eq!(
+            StorageSnapshot::from_hex("0A ff\n").unwrap().as_bytes(),
+            [0x0a, 0xff]
+        );
+
+        assert!(StorageSnapshot::from_hex("0").is_err());
+        assert!(StorageSnapshot::from_hex("0g").is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_fixture_files() {
+        let snapshot = StorageSnapshot::new((0..100).collect());
+        let dir = std::env::temp_dir();
+        let hex_path = dir.join(alloc::format!("kelk-snapshot-{}.hex", std::process::id()));
+        let bin_path = dir.join(alloc::format!("kelk-snapshot-{}.bin", std::process::id()));
+
+        snapshot.save_hex(&hex_path).unwrap();
+        snapshot.save_binary(&bin_path).unwrap();
+        assert_eq!(StorageSnapshot::load_hex(&hex_path).unwrap(), snapshot);
+        assert_eq!(StorageSnapshot::load_binary(&bin_path).unwrap(), snapshot);
+
+        std::fs::write(&hex_path, "xyz").unwrap();
+        assert_eq!(
+            StorageSnapshot::load_hex(&hex_path).unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        std::fs::remove_file(hex_path).unwrap();
+        std::fs::remove_file(bin_path).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+//! A panic handler that forwards the panic message to the host.
+//!
+//! Without it a panicking contract only raises an `unreachable` trap and the host
+//! can't tell why the execution failed. The handler formats the message into a
+//! fixed buffer, so it works even if the panic is caused by running out of memory.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+
+/// The maximum length of the panic message that is reported to the host.
+/// Longer messages are truncated.
+const MAX_MSG_LEN: usize = 1024;
+
+struct MsgBuffer {
+    buf: [u8; MAX_MSG_LEN],
+    len: usize,
+}
+
+impl MsgBuffer {
+    fn new() -> Self {
+        Self {
+            buf: [0; MAX_MSG_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // The buffer only contains whole UTF-8 characters, see `write_str`.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+impl Write for MsgBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let free = MAX_MSG_LEN - self.len;
+        let mut len = s.len().min(free);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.buf[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let mut msg = MsgBuffer::new();
+    let _ = write!(msg, "{}", info.message());
+
+    match info.location() {
+        Some(loc) => crate::import::panic(msg.as_str(), loc.file(), loc.line(), loc.column()),
+        None => crate::import::panic(msg.as_str(), "", 0, 0),
+    }
+
+    core::arch::wasm32::unreachable()
+}
@@ -0,0 +1,165 @@
+//! The response of instantiate and process calls
+//!
+//! Besides the success or failure of the call, a contract can return some data,
+//! emit events, attach key/value attributes and ask the host to send follow-up
+//! messages to other contracts.
+
+use crate::address::Address;
+use crate::error::Error;
+use crate::Schema;
+use alloc::string::String;
+use alloc::vec::Vec;
+use minicbor::{Decode, Encode};
+
+/// A key/value pair that describes the execution of a call.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct Attribute {
+    /// The key of the attribute
+    #[n(0)]
+    pub key: String,
+    /// The value of the attribute
+    #[n(1)]
+    pub value: String,
+}
+
+impl Attribute {
+    /// creates a new attribute
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+}
+
+/// An event emitted by the contract.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct Event {
+    /// The kind of the event, like "transfer"
+    #[n(0)]
+    pub kind: String,
+    /// The attributes of the event
+    #[n(1)]
+    pub attributes: Vec<Attribute>,
+}
+
+impl Event {
+    /// creates a new event with no attributes
+    pub fn new(kind: impl Into<String>) -> Self {
+        Self {
+            kind: kind.into(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// adds an attribute to the event
+    pub fn add_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push(Attribute::new(key, value));
+        self
+    }
+}
+
+/// A follow-up message that the host sends to another contract
+/// after the current call is executed successfully.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct SubMessage {
+    /// The address of the contract that receives the message
+    #[n(0)]
+    pub contract: Address,
+    /// The CBOR encoded process message
+    #[cbor(n(1), with = "minicbor::bytes")]
+    pub msg: Vec<u8>,
+}
+
+impl SubMessage {
+    /// creates a new message by encoding `msg` for the given contract.
+    /// It returns an error if the message can't be encoded.
+    pub fn new<M: Encode>(contract: Address, msg: &M) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        minicbor::encode(msg, &mut buf).map_err(|_| Error::GenericError("encoding failed"))?;
+        Ok(Self { contract, msg: buf })
+    }
+}
+
+/// The response of instantiate and process calls.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Encode, Decode, Schema)]
+pub struct Response {
+    /// The data returned to the caller
+    #[cbor(n(0), with = "minicbor::bytes")]
+    pub data: Option<Vec<u8>>,
+    /// The events emitted during the execution
+    #[n(1)]
+    pub events: Vec<Event>,
+    /// The attributes that describe the execution
+    #[n(2)]
+    pub attributes: Vec<Attribute>,
+    /// The messages that should be sent to other contracts
+    #[n(3)]
+    pub messages: Vec<SubMessage>,
+}
+
+impl Response {
+    /// creates an empty response
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sets the data returned to the caller
+    pub fn set_data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// adds an event to the response
+    pub fn add_event(mut self, event: Event) -> Self {
+        self.events.push(event);
+        self
+    }
+
+    /// adds an attribute to the response
+    pub fn add_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.push(Attribute::new(key, value));
+        self
+    }
+
+    /// adds a follow-up message to the response
+    pub fn add_message(mut self, msg: SubMessage) -> Self {
+        self.messages.push(msg);
+        self
+    }
+}
+
+impl From<()> for Response {
+    fn from(_: ()) -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_empty_response() {
+        let mut buf = Vec::new();
+        minicbor::encode(Response::new(), &mut buf).unwrap();
+        assert_eq!(buf, vec![0x84, 0xf6, 0x80, 0x80, 0x80]); // http://cbor.me/?bytes=84f6808080
+    }
+
+    #[test]
+    fn test_response() {
+        let res = Response::new()
+            .set_data(vec![1, 2, 3])
+            .add_attribute("action", "add")
+            .add_event(Event::new("transfer").add_attribute("amount", "10"))
+            .add_message(SubMessage::new(Address::from([1; 21]), &0x0e_i32).unwrap());
+
+        let mut buf = Vec::new();
+        minicbor::encode(&res, &mut buf).unwrap();
+        let decoded: Response = minicbor::decode(&buf).unwrap();
+
+        assert_eq!(decoded, res);
+        assert_eq!(decoded.messages[0].msg, vec![0x0e]);
+    }
+}
@@ -0,0 +1,45 @@
+//! The default runtime for the contracts.
+//!
+//! With the `runtime` feature, a contract crate doesn't need to provide
+//! the panic handler and the global allocator, and it builds on stable Rust.
+//! The panic handler is provided by the `panic-handler` feature.
+//! If an allocation fails, the default allocation error handler panics,
+//! so the host receives the failure like any other panic.
+
+use crate::allocator::BumpAllocator;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// Use the bump allocator as the global allocator.
+#[global_allocator]
+static ALLOC: BumpAllocator = BumpAllocator::new();
+
+/// The peak memory that is used by the last call
+static LAST_PEAK: AtomicU32 = AtomicU32::new(0);
+
+/// resets the arena if no buffer is handed out to the host.
+/// It is called at the entry and exit of the exported functions, so an allocation
+/// that is leaked by a call doesn't keep the memory of the next calls.
+pub(crate) fn reset_arena() {
+    if !crate::memory::has_live_buffers() {
+        ALLOC.reset();
+    }
+}
+
+/// resets the arena and starts measuring the memory of an entry point call
+pub(crate) fn begin_call() {
+    reset_arena();
+    ALLOC.reset_peak();
+}
+
+/// records the peak memory of the entry point call and resets the arena
+pub(crate) fn end_call() {
+    LAST_PEAK.store(ALLOC.stats().peak as u32, Ordering::Relaxed);
+    reset_arena();
+}
+
+/// returns the peak memory in bytes that is used by the last entry point call,
+/// including the message and the result buffers.
+#[no_mangle]
+extern "C" fn kelk_peak_memory() -> u32 {
+    LAST_PEAK.load(Ordering::Relaxed)
+}
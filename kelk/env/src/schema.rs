@@ -0,0 +1,451 @@
+//! The schema of the contract's messages
+//!
+//! The schema describes the field names, CBOR tags and types of the messages,
+//! so clients can encode the calls without copying the tag numbers by hand.
+//! It can be derived by `#[derive(Schema)]` and written as JSON on the host target,
+//! for example by an `examples/schema.rs` in the contract crate:
+//!
+//! ```ignore
+//! fn main() {
+//!     let schema = ContractSchema::new("calculator", "0.1.0")
+//!         .process::<ProcessMsg>()
+//!         .query::<QueryMsg, QueryRsp>()
+//!         .error::<Error>();
+//!     std::fs::write("schema.json", schema.to_json()).unwrap();
+//! }
+//! ```
+
+use crate::address::Address;
+use crate::amount::Amount;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// The schema of a type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaType {
+    /// A primitive type, like "u32", "bool", "string" or "bytes"
+    Primitive(&'static str),
+    /// An optional value, encoded as the value or null
+    Option(Box<SchemaType>),
+    /// A CBOR array of values
+    Array(Box<SchemaType>),
+    /// A struct, encoded as a CBOR array in which each field is placed at its tag
+    Struct {
+        /// The name of the struct
+        name: &'static str,
+        /// The fields of the struct
+        fields: Vec<Field>,
+    },
+    /// An enum, encoded as a two-element array of the tag and the variant's fields
+    Enum {
+        /// The name of the enum
+        name: &'static str,
+        /// The variants of the enum
+        variants: Vec<Variant>,
+    },
+}
+
+/// A field of a struct or an enum variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field. It is `None` for tuple fields.
+    pub name: Option<&'static str>,
+    /// The CBOR tag of the field
+    pub tag: u32,
+    /// The type of the field
+    pub ty: SchemaType,
+}
+
+/// A variant of an enum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    /// The name of the variant
+    pub name: &'static str,
+    /// The CBOR tag of the variant
+    pub tag: u32,
+    /// The fields of the variant
+    pub fields: Vec<Field>,
+}
+
+/// The types that can describe their schema
+pub trait Schema {
+    /// returns the schema of the type
+    fn schema() -> SchemaType;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $name:literal),*) => {
+        $(
+            impl Schema for $ty {
+                fn schema() -> SchemaType {
+                    SchemaType::Primitive($name)
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive!(
+    () => "null",
+    bool => "bool",
+    u8 => "u8",
+    u16 => "u16",
+    u32 => "u32",
+    u64 => "u64",
+    i8 => "i8",
+    i16 => "i16",
+    i32 => "i32",
+    i64 => "i64",
+    f32 => "f32",
+    f64 => "f64",
+    str => "string",
+    String => "string",
+    Address => "address",
+    Amount => "amount"
+);
+
+impl<T: Schema + ?Sized> Schema for &T {
+    fn schema() -> SchemaType {
+        T::schema()
+    }
+}
+
+impl<T: Schema + ?Sized> Schema for Box<T> {
+    fn schema() -> SchemaType {
+        T::schema()
+    }
+}
+
+impl<T: Schema> Schema for Option<T> {
+    fn schema() -> SchemaType {
+        SchemaType::Option(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema> Schema for Vec<T> {
+    fn schema() -> SchemaType {
+        SchemaType::Array(Box::new(T::schema()))
+    }
+}
+
+impl<T: Schema, const N: usize> Schema for [T; N] {
+    fn schema() -> SchemaType {
+        SchemaType::Array(Box::new(T::schema()))
+    }
+}
+
+/// The schema of a contract's interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractSchema {
+    /// The name of the contract
+    pub name: String,
+    /// The version of the contract
+    pub version: String,
+    /// The instantiate message
+    pub instantiate: Option<SchemaType>,
+    /// The process message
+    pub process: Option<SchemaType>,
+    /// The query message
+    pub query: Option<SchemaType>,
+    /// The query response.
+    /// For the contracts that use `kelk_contract`, it has a variant for each query
+    /// with the same tag.
+    pub response: Option<SchemaType>,
+    /// The migrate message
+    pub migrate: Option<SchemaType>,
+    /// The error returned by the contract
+    pub error: Option<SchemaType>,
+}
+
+impl ContractSchema {
+    /// creates an empty schema for the given contract
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            instantiate: None,
+            process: None,
+            query: None,
+            response: None,
+            migrate: None,
+            error: None,
+        }
+    }
+
+    /// sets the instantiate message
+    pub fn instantiate<M: Schema>(mut self) -> Self {
+        self.instantiate = Some(M::schema());
+        self
+    }
+
+    /// sets the process message
+    pub fn process<M: Schema>(mut self) -> Self {
+        self.process = Some(M::schema());
+        self
+    }
+
+    /// sets the query message and its response
+    pub fn query<M: Schema, R: Schema>(mut self) -> Self {
+        self.query = Some(M::schema());
+        self.response = Some(R::schema());
+        self
+    }
+
+    /// sets the migrate message
+    pub fn migrate<M: Schema>(mut self) -> Self {
+        self.migrate = Some(M::schema());
+        self
+    }
+
+    /// sets the error type
+    pub fn error<E: Schema>(mut self) -> Self {
+        self.error = Some(E::schema());
+        self
+    }
+
+    /// returns the schema as JSON
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push('{');
+        write_key(&mut out, "name");
+        write_str(&mut out, &self.name);
+        out.push(',');
+        write_key(&mut out, "version");
+        write_str(&mut out, &self.version);
+        for (key, ty) in [
+            ("instantiate", &self.instantiate),
+            ("process", &self.process),
+            ("query", &self.query),
+            ("response", &self.response),
+            ("migrate", &self.migrate),
+            ("error", &self.error),
+        ] {
+            out.push(',');
+            write_key(&mut out, key);
+            match ty {
+                Some(ty) => write_type(&mut out, ty),
+                None => out.push_str("null"),
+            }
+        }
+        out.push('}');
+        out
+    }
+}
+
+impl SchemaType {
+    /// returns the schema as JSON
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write_type(&mut out, self);
+        out
+    }
+}
+
+fn write_type(out: &mut String, ty: &SchemaType) {
+    match ty {
+        SchemaType::Primitive(name) => write_str(out, name),
+        SchemaType::Option(inner) => {
+            out.push('{');
+            write_key(out, "option");
+            write_type(out, inner);
+            out.push('}');
+        }
+        SchemaType::Array(inner) => {
+            out.push('{');
+            write_key(out, "array");
+            write_type(out, inner);
+            out.push('}');
+        }
+        SchemaType::Struct { name, fields } => {
+            out.push('{');
+            write_key(out, "struct");
+            write_str(out, name);
+            out.push(',');
+            write_fields(out, fields);
+            out.push('}');
+        }
+        SchemaType::Enum { name, variants } => {
+            out.push('{');
+            write_key(out, "enum");
+            write_str(out, name);
+            out.push(',');
+            write_key(out, "variants");
+            out.push('[');
+            for (i, variant) in variants.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push('{');
+                write_key(out, "name");
+                write_str(out, variant.name);
+                out.push(',');
+                write_key(out, "tag");
+                let _ = write!(out, "{}", variant.tag);
+                out.push(',');
+                write_fields(out, &variant.fields);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+    }
+}
+
+fn write_fields(out: &mut String, fields: &[Field]) {
+    write_key(out, "fields");
+    out.push('[');
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('{');
+        if let Some(name) = field.name {
+            write_key(out, "name");
+            write_str(out, name);
+            out.push(',');
+        }
+        write_key(out, "tag");
+        let _ = write!(out, "{}", field.tag);
+        out.push(',');
+        write_key(out, "type");
+        write_type(out, &field.ty);
+        out.push('}');
+    }
+    out.push(']');
+}
+
+fn write_key(out: &mut String, key: &str) {
+    write_str(out, key);
+    out.push(':');
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::Response;
+    use crate::Schema;
+    use minicbor::{Decode, Encode};
+
+    #[allow(dead_code)]
+    #[derive(Encode, Decode, Schema)]
+    enum ProcessMsg {
+        #[n(0)]
+        Add {
+            #[n(0)]
+            a: i32,
+            #[n(1)]
+            b: Option<u8>,
+        },
+        #[n(3)]
+        Store(#[cbor(n(0), with = "minicbor::bytes")] Vec<u8>),
+        #[n(4)]
+        Reset,
+    }
+
+    #[test]
+    fn test_derive() {
+        assert_eq!(
+            ProcessMsg::schema(),
+            SchemaType::Enum {
+                name: "ProcessMsg",
+                variants: alloc::vec![
+                    Variant {
+                        name: "Add",
+                        tag: 0,
+                        fields: alloc::vec![
+                            Field {
+                                name: Some("a"),
+                                tag: 0,
+                                ty: SchemaType::Primitive("i32"),
+                            },
+                            Field {
+                                name: Some("b"),
+                                tag: 1,
+                                ty: SchemaType::Option(Box::new(SchemaType::Primitive("u8"))),
+                            },
+                        ],
+                    },
+                    Variant {
+                        name: "Store",
+                        tag: 3,
+                        fields: alloc::vec![Field {
+                            name: None,
+                            tag: 0,
+                            ty: SchemaType::Primitive("bytes"),
+                        }],
+                    },
+                    Variant {
+                        name: "Reset",
+                        tag: 4,
+                        fields: Vec::new(),
+                    },
+                ],
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, Eq, Encode, Decode, Schema)]
+    struct Transfer {
+        #[n(0)]
+        to: Address,
+        #[n(1)]
+        amount: Amount,
+    }
+
+    #[test]
+    fn test_amount() {
+        let SchemaType::Struct { fields, .. } = Transfer::schema() else {
+            panic!("expected struct");
+        };
+        assert_eq!(fields[1].ty, SchemaType::Primitive("amount"));
+
+        // The message with an amount can be encoded like its schema describes.
+        let msg = Transfer {
+            to: Address::from([1; 21]),
+            amount: Amount::MAX,
+        };
+        let mut buf = Vec::new();
+        minicbor::encode(&msg, &mut buf).unwrap();
+        assert_eq!(minicbor::decode::<Transfer>(&buf).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_response() {
+        let SchemaType::Struct { name, fields } = Response::schema() else {
+            panic!("expected struct");
+        };
+        assert_eq!(name, "Response");
+        assert_eq!(
+            fields[0].ty,
+            SchemaType::Option(Box::new(SchemaType::Primitive("bytes")))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let schema = ContractSchema::new("calc\"ulator", "0.1.0").process::<ProcessMsg>();
+        assert_eq!(
+            schema.to_json(),
+            r#"{"name":"calc\"ulator","version":"0.1.0","instantiate":null,"process":{"enum":"ProcessMsg","variants":[{"name":"Add","tag":0,"fields":[{"name":"a","tag":0,"type":"i32"},{"name":"b","tag":1,"type":{"option":"u8"}}]},{"name":"Store","tag":3,"fields":[{"tag":0,"type":"bytes"}]},{"name":"Reset","tag":4,"fields":[]}]},"query":null,"response":null,"migrate":null,"error":null}"#
+        );
+    }
+}
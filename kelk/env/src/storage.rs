@@ -34,6 +34,33 @@ macro_rules! impl_num {
     };
 }
 
+/// The size of the area at the beginning of the storage file which is reserved for Kelk.
+/// The offsets used by the contract start right after this area.
+pub const RESERVED_SIZE: u32 = 256;
+
+/// The version of the storage layout that has the reserved area.
+/// The storage of the contracts that are deployed before it has no layout header
+/// and its version is `LEGACY_LAYOUT_VERSION`.
+pub const LAYOUT_VERSION: u8 = 1;
+
+/// The version of the storage layout without the reserved area.
+/// The contract's data starts at the beginning of the storage file.
+pub const LEGACY_LAYOUT_VERSION: u8 = 0;
+
+/// The size of the layout header at the beginning of the reserved area
+pub(crate) const LAYOUT_HEADER_SIZE: u32 = 8;
+
+/// The magic bytes at the beginning of the layout header
+const LAYOUT_MAGIC: &[u8; 4] = b"KELK";
+
+/// returns the layout header: the magic bytes, followed by the layout version
+pub(crate) fn layout_header() -> [u8; LAYOUT_HEADER_SIZE as usize] {
+    let mut header = [0; LAYOUT_HEADER_SIZE as usize];
+    header[..4].copy_from_slice(LAYOUT_MAGIC);
+    header[4] = LAYOUT_VERSION;
+    header
+}
+
 /// the storage APIs that provided by the host
 pub trait StorageAPI {
     /// writes `data` into the storage file at the given offset
@@ -122,18 +149,82 @@ impl Storage {
 
     /// writes `data` into the storage file at the given offset
     pub fn read(&self, offset: u32, len: u32) -> Result<Vec<u8>, Error> {
-        self.api.read(offset, len)
+        self.api.read(Self::contract_offset(offset)?, len)
     }
 
     /// reads `data` from the storage file at the given offset and length
     pub fn write(&self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        self.api.write(Self::contract_offset(offset)?, data)
+    }
+
+    /// reads `data` from the reserved area at the given offset and length
+    pub(crate) fn read_reserved(&self, offset: u32, len: u32) -> Result<Vec<u8>, Error> {
+        if offset as usize + len as usize > RESERVED_SIZE as usize {
+            return Err(Error::GenericError("overflowed"));
+        }
+        self.api.read(offset, len)
+    }
+
+    /// writes `data` into the reserved area at the given offset
+    pub(crate) fn write_reserved(&self, offset: u32, data: &[u8]) -> Result<(), Error> {
+        if offset as usize + data.len() > RESERVED_SIZE as usize {
+            return Err(Error::GenericError("overflowed"));
+        }
         self.api.write(offset, data)
     }
+
+    /// returns the version of the storage layout.
+    /// It returns `LEGACY_LAYOUT_VERSION` if the storage has no layout header,
+    /// like the storage of the contracts that are deployed before the reserved area
+    /// or the contracts that are not instantiated yet.
+    pub fn layout_version(&self) -> Result<u8, Error> {
+        let header = self.api.read(0, LAYOUT_HEADER_SIZE)?;
+        if &header[..4] != LAYOUT_MAGIC {
+            return Ok(LEGACY_LAYOUT_VERSION);
+        }
+        Ok(header[4])
+    }
+
+    /// returns true if the storage has no layout header and its reserved area is all zeros,
+    /// like the storage of a new contract.
+    /// A legacy contract with zeros in its first `RESERVED_SIZE` bytes looks the same.
+    pub(crate) fn is_blank(&self) -> Result<bool, Error> {
+        Ok(self.layout_version()? == LEGACY_LAYOUT_VERSION
+            && self.api.read(0, RESERVED_SIZE)?.iter().all(|b| *b == 0))
+    }
+
+    /// writes the layout header of the current layout version
+    pub(crate) fn write_layout_header(&self) -> Result<(), Error> {
+        self.write_reserved(0, &layout_header())
+    }
+
+    /// converts the storage of a legacy contract to the current layout.
+    /// It moves the first `size` bytes of the storage file after the reserved area,
+    /// so they can be read at the same offsets as before,
+    /// clears the reserved area and writes the layout header.
+    /// The storage file should have `RESERVED_SIZE` bytes free at the end.
+    pub fn migrate_layout(&self, size: u32) -> Result<(), Error> {
+        if self.layout_version()? != LEGACY_LAYOUT_VERSION {
+            return Err(Error::GenericError("storage layout is already migrated"));
+        }
+        let data = self.api.read(0, size)?;
+        self.write(0, &data)?;
+        self.api.write(0, &[0; RESERVED_SIZE as usize])?;
+        self.write_layout_header()
+    }
+
+    /// converts the contract's offset to the offset in the storage file
+    fn contract_offset(offset: u32) -> Result<u32, Error> {
+        offset
+            .checked_add(RESERVED_SIZE)
+            .ok_or(Error::GenericError("overflowed"))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::mock::mock_storage;
+    use super::*;
+    use crate::mock::{mock_storage, MockStorageAPI};
 
     #[test]
     fn test_negative_integers() {
@@ -173,6 +264,52 @@ mod tests {
         assert!(mock.read_bool(0).unwrap());
     }
 
+    #[test]
+    fn test_overflow() {
+        let mock = mock_storage(4);
+
+        assert!(mock.write_u32(1, 1).is_err());
+        assert!(mock.read_u32(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_layout() {
+        let storage = Storage::new(Box::new(MockStorageAPI::new(RESERVED_SIZE as usize + 16)));
+        assert_eq!(storage.layout_version().unwrap(), LEGACY_LAYOUT_VERSION);
+        storage.write_layout_header().unwrap();
+        assert_eq!(storage.layout_version().unwrap(), LAYOUT_VERSION);
+        assert_eq!(mock_storage(0).layout_version().unwrap(), LAYOUT_VERSION);
+        assert!(!mock_storage(0).is_blank().unwrap());
+
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 16);
+        let storage = Storage::new(Box::new(api.clone()));
+        assert!(storage.is_blank().unwrap());
+        // The data of a legacy contract
+        api.write(RESERVED_SIZE - 1, &[1]).unwrap();
+        assert!(!storage.is_blank().unwrap());
+    }
+
+    #[test]
+    fn test_migrate_layout() {
+        let api = MockStorageAPI::new(16 + RESERVED_SIZE as usize);
+        let storage = Storage::new(Box::new(api.clone()));
+        // The legacy contract writes its data at the beginning of the storage file.
+        api.write(0, &[0xff; 16]).unwrap();
+        api.write(0, &0x01020304_u32.to_be_bytes()).unwrap();
+        assert_eq!(storage.layout_version().unwrap(), LEGACY_LAYOUT_VERSION);
+
+        storage.migrate_layout(16).unwrap();
+        assert_eq!(storage.layout_version().unwrap(), LAYOUT_VERSION);
+        assert_eq!(storage.read_u32(0).unwrap(), 0x01020304);
+        assert_eq!(storage.read(4, 12).unwrap(), [0xff; 12]);
+        assert_eq!(
+            storage.read_reserved(LAYOUT_HEADER_SIZE, 8).unwrap(),
+            [0; 8]
+        );
+
+        assert!(storage.migrate_layout(16).is_err());
+    }
+
     #[test]
     fn test_struct() {
         #[derive(Debug, PartialEq)]
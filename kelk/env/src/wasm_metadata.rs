@@ -0,0 +1,290 @@
+//! The metadata that is embedded in a custom section of the contract's wasm file
+//!
+//! Kelk-env and the code generated by `kelk_derive` and `kelk_contract` put
+//! entries into the [`SECTION_NAME`] custom section. The linker concatenates
+//! the sections with the same name, so the section is a sequence of entries.
+//! Each entry is a key and a value, both prefixed by their length as a byte.
+//!
+//! The host can read the metadata by [`WasmMetadata::from_wasm`] and check
+//! the compatibility of the contract before calling it.
+
+use crate::error::Error;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The name of the custom section
+pub const SECTION_NAME: &str = "kelk_metadata";
+
+/// The version of the interface between the host and the contract.
+/// It covers the host imports and the memory layout of the pointers.
+/// The contract returns it from the `kelk_interface_version` export.
+pub const ABI_VERSION: u32 = 2;
+
+/// The name of the module that the host functions are imported from.
+/// It is versioned with [`ABI_VERSION`], so the host can provide
+/// several revisions of the interface side by side.
+pub const IMPORT_MODULE: &str = "zarb_v2";
+
+/// The key of the kelk-env version entry
+pub const KEY_KELK_VERSION: &str = "kelk_env_version";
+/// The key of the ABI version entry. The value is a little-endian `u32`.
+pub const KEY_ABI_VERSION: &str = "abi_version";
+/// The key of the contract name entry
+pub const KEY_CONTRACT_NAME: &str = "contract_name";
+/// The key of the contract version entry
+pub const KEY_CONTRACT_VERSION: &str = "contract_version";
+/// The key of the exported entry point, like "process"
+pub const KEY_EXPORT: &str = "export";
+
+/// returns the length of the encoded entry
+#[doc(hidden)]
+pub const fn entry_len(key: &str, value: &[u8]) -> usize {
+    key.len() + value.len() + 2
+}
+
+/// encodes the entry. It fails at compile time if the key or the value is too long.
+#[doc(hidden)]
+pub const fn entry<const N: usize>(key: &str, value: &[u8]) -> [u8; N] {
+    let key = key.as_bytes();
+    assert!(key.len() <= u8::MAX as usize, "metadata key is too long");
+    assert!(
+        value.len() <= u8::MAX as usize,
+        "metadata value is too long"
+    );
+    assert!(N == key.len() + value.len() + 2, "invalid metadata length");
+
+    let mut buf = [0; N];
+    buf[0] = key.len() as u8;
+    let mut i = 0;
+    while i < key.len() {
+        buf[1 + i] = key[i];
+        i += 1;
+    }
+    buf[1 + key.len()] = value.len() as u8;
+    let mut i = 0;
+    while i < value.len() {
+        buf[2 + key.len() + i] = value[i];
+        i += 1;
+    }
+    buf
+}
+
+/// Puts an entry into the metadata section of the wasm file.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __wasm_metadata_entry {
+    ($name:ident, $key:expr, $value:expr) => {
+        #[cfg(target_arch = "wasm32")]
+        #[link_section = "kelk_metadata"]
+        #[used]
+        static $name: [u8; $crate::wasm_metadata::entry_len($key, $value)] =
+            $crate::wasm_metadata::entry($key, $value);
+    };
+}
+
+/// The metadata of a contract that is read from its wasm file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WasmMetadata {
+    /// The version of kelk-env that the contract is built with
+    pub kelk_version: Option<String>,
+    /// The version of the interface between the host and the contract
+    pub abi_version: Option<u32>,
+    /// The name of the contract
+    pub contract_name: Option<String>,
+    /// The version of the contract
+    pub contract_version: Option<String>,
+    /// The exported entry points, like "instantiate" and "process"
+    pub exports: Vec<String>,
+}
+
+impl WasmMetadata {
+    /// reads the metadata from the wasm file.
+    /// It returns the default metadata if the file doesn't have the metadata section.
+    pub fn from_wasm(wasm: &[u8]) -> Result<Self, Error> {
+        let mut metadata = WasmMetadata::default();
+        let mut reader = Reader::new(wasm);
+        if reader.bytes(4)? != b"\0asm" {
+            return Err(Error::GenericError("invalid wasm magic"));
+        }
+        if reader.bytes(4)? != [1, 0, 0, 0] {
+            return Err(Error::GenericError("unsupported wasm version"));
+        }
+        while !reader.is_empty() {
+            let id = reader.byte()?;
+            let size = reader.leb128()? as usize;
+            let mut section = Reader::new(reader.bytes(size)?);
+            if id != 0 {
+                continue;
+            }
+            let name_len = section.leb128()? as usize;
+            if section.bytes(name_len)? == SECTION_NAME.as_bytes() {
+                metadata.read_entries(&mut section)?;
+            }
+        }
+        Ok(metadata)
+    }
+
+    fn read_entries(&mut self, reader: &mut Reader) -> Result<(), Error> {
+        while !reader.is_empty() {
+            let key_len = reader.byte()? as usize;
+            let key = reader.bytes(key_len)?;
+            let value_len = reader.byte()? as usize;
+            let value = reader.bytes(value_len)?;
+            let key = core::str::from_utf8(key)
+                .map_err(|_| Error::GenericError("invalid metadata key"))?;
+            match key {
+                KEY_ABI_VERSION => {
+                    let value = value
+                        .try_into()
+                        .map_err(|_| Error::GenericError("invalid abi version"))?;
+                    Self::set(&mut self.abi_version, u32::from_le_bytes(value))?;
+                }
+                KEY_KELK_VERSION => Self::set(&mut self.kelk_version, to_string(value)?)?,
+                KEY_CONTRACT_NAME => Self::set(&mut self.contract_name, to_string(value)?)?,
+                KEY_CONTRACT_VERSION => Self::set(&mut self.contract_version, to_string(value)?)?,
+                KEY_EXPORT => {
+                    let export = to_string(value)?;
+                    if !self.exports.contains(&export) {
+                        self.exports.push(export);
+                    }
+                }
+                // Unknown keys are ignored for forward compatibility.
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// sets the value of the entry. Since the entry functions of `kelk_derive` put the
+    /// contract name and version too, an entry can be repeated but it can't have
+    /// different values.
+    fn set<T: PartialEq>(entry: &mut Option<T>, value: T) -> Result<(), Error> {
+        match entry {
+            Some(old) if *old != value => Err(Error::GenericError("conflicting metadata")),
+            _ => {
+                *entry = Some(value);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn to_string(value: &[u8]) -> Result<String, Error> {
+    core::str::from_utf8(value)
+        .map(Into::into)
+        .map_err(|_| Error::GenericError("invalid metadata value"))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.buf.len() {
+            return Err(Error::GenericError("unexpected end of wasm"));
+        }
+        let (bytes, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn leb128(&mut self) -> Result<u32, Error> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32) << (i * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::GenericError("invalid leb128"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn wasm_with_section(payload: &[u8]) -> Vec<u8> {
+        let mut wasm = vec![0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        // A type section that should be skipped
+        wasm.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+        wasm.push(0x00);
+        wasm.push((SECTION_NAME.len() + 1 + payload.len()) as u8);
+        wasm.push(SECTION_NAME.len() as u8);
+        wasm.extend_from_slice(SECTION_NAME.as_bytes());
+        wasm.extend_from_slice(payload);
+        wasm
+    }
+
+    #[test]
+    fn test_import_module() {
+        assert_eq!(IMPORT_MODULE, alloc::format!("zarb_v{}", ABI_VERSION));
+    }
+
+    #[test]
+    fn test_entry() {
+        const ENTRY: [u8; entry_len("export", b"query")] = entry("export", b"query");
+        assert_eq!(ENTRY, *b"\x06export\x05query");
+    }
+
+    #[test]
+    fn test_from_wasm() {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&entry::<{ entry_len(KEY_KELK_VERSION, b"0.2.2") }>(
+            KEY_KELK_VERSION,
+            b"0.2.2",
+        ));
+        payload.extend_from_slice(&entry::<{ entry_len(KEY_ABI_VERSION, &[1, 0, 0, 0]) }>(
+            KEY_ABI_VERSION,
+            &1u32.to_le_bytes(),
+        ));
+        for export in [&b"process"[..], b"query", b"process"] {
+            payload.extend_from_slice(&[6]);
+            payload.extend_from_slice(b"export");
+            payload.push(export.len() as u8);
+            payload.extend_from_slice(export);
+        }
+        payload.extend_from_slice(b"\x07unknown\x00");
+
+        let metadata = WasmMetadata::from_wasm(&wasm_with_section(&payload)).unwrap();
+        assert_eq!(metadata.kelk_version.as_deref(), Some("0.2.2"));
+        assert_eq!(metadata.abi_version, Some(1));
+        assert_eq!(metadata.contract_name, None);
+        assert_eq!(metadata.exports, vec!["process", "query"]);
+    }
+
+    #[test]
+    fn test_conflict() {
+        let payload = b"\x0dcontract_name\x03foo\x0dcontract_name\x03bar";
+        assert!(WasmMetadata::from_wasm(&wasm_with_section(payload)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_wasm() {
+        assert!(WasmMetadata::from_wasm(b"\0asm").is_err());
+        assert!(WasmMetadata::from_wasm(b"\0elf\x01\0\0\0").is_err());
+        assert_eq!(
+            WasmMetadata::from_wasm(b"\0asm\x01\0\0\0").unwrap(),
+            WasmMetadata::default()
+        );
+
+        let mut wasm = wasm_with_section(b"\x06export\x05query");
+        wasm.pop();
+        assert!(WasmMetadata::from_wasm(&wasm).is_err());
+    }
+}
@@ -0,0 +1,318 @@
+//! Loading and calling a compiled contract
+
+use crate::error::Error;
+use crate::imports::{self, HostState};
+use kelk_env::blockchain::Blockchain;
+use kelk_env::context::OwnedContext;
+use kelk_env::wasm_metadata::{WasmMetadata, ABI_VERSION};
+use wasmi::{Engine, Instance, Linker, Memory, Module, Store, TypedFunc, WasmParams, WasmResults};
+
+/// A contract that is loaded from its wasm file.
+///
+/// The calls follow the same steps as the chain: the message is copied into a buffer
+/// that is allocated by the contract's `allocate` export, the entry point is called
+/// with the pointer of the message, and at the end the message and the result
+/// are freed by the contract's `deallocate` export.
+pub struct Contract<B: Blockchain + 'static> {
+    store: Store<HostState<B>>,
+    instance: Instance,
+    memory: Memory,
+    metadata: WasmMetadata,
+}
+
+impl<B: Blockchain + 'static> Contract<B> {
+    /// loads the contract from its wasm file with the given context.
+    /// It fails if the contract is built for another version of the host interface.
+    pub fn new(wasm: &[u8], ctx: OwnedContext<B>) -> Result<Self, Error> {
+        let metadata =
+            WasmMetadata::from_wasm(wasm).map_err(|err| Error::Wasm(format!("{:?}", err)))?;
+
+        let engine = Engine::default();
+        let module = Module::new(&engine, wasm)?;
+        let mut store = Store::new(&engine, HostState { ctx, panic: None });
+        let mut linker = Linker::new(&engine);
+        imports::link(&mut linker)?;
+        let instance = linker
+            .instantiate(&mut store, &module)?
+            .ensure_no_start(&mut store)
+            .map_err(|err| Error::Wasm(err.to_string()))?;
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| Error::MissingExport("memory".into()))?;
+
+        let mut contract = Self {
+            store,
+            instance,
+            memory,
+            metadata,
+        };
+        let version: u32 = contract.call_export("kelk_interface_version", ())?;
+        if version != ABI_VERSION {
+            return Err(Error::IncompatibleVersion(version));
+        }
+        Ok(contract)
+    }
+
+    /// returns the metadata that is embedded in the wasm file
+    pub fn metadata(&self) -> &WasmMetadata {
+        &self.metadata
+    }
+
+    /// returns the context of the contract, for example to check its storage
+    pub fn context(&self) -> &OwnedContext<B> {
+        &self.store.data().ctx
+    }
+
+    /// returns the context of the contract as mutable, for example to set the block number
+    pub fn context_mut(&mut self) -> &mut OwnedContext<B> {
+        &mut self.store.data_mut().ctx
+    }
+
+    /// calls the `instantiate` entry point with the CBOR encoded message
+    /// and returns the CBOR encoded envelope.
+    pub fn instantiate(&mut self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call("instantiate", msg)
+    }
+
+    /// calls the `process` entry point with the CBOR encoded message
+    /// and returns the CBOR encoded envelope.
+    pub fn process(&mut self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call("process", msg)
+    }
+
+    /// calls the `query` entry point with the CBOR encoded message
+    /// and returns the CBOR encoded envelope.
+    pub fn query(&mut self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call("query", msg)
+    }
+
+    /// calls the `migrate` entry point with the CBOR encoded message
+    /// and returns the CBOR encoded envelope.
+    pub fn migrate(&mut self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.call("migrate", msg)
+    }
+
+    /// returns the peak memory used by the last call,
+    /// if the contract is built with the runtime of kelk-env.
+    pub fn peak_memory(&mut self) -> Option<u32> {
+        self.call_export("kelk_peak_memory", ()).ok()
+    }
+
+    /// calls the entry point with the CBOR encoded message and returns the result.
+    /// The message and the result are freed even if the call fails.
+    pub fn call(&mut self, entry: &str, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let msg_len = u32::try_from(msg.len()).map_err(|_| Error::Wasm("too long".into()))?;
+        let msg_ptr: u64 = self.call_export("allocate", msg_len)?;
+
+        let res = self.call_entry(entry, msg_ptr, msg);
+        let freed = self.deallocate(msg_ptr);
+        let res = res?;
+        freed?;
+        Ok(res)
+    }
+
+    /// writes the message at `msg_ptr`, calls the entry point and reads the result
+    fn call_entry(&mut self, entry: &str, msg_ptr: u64, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        self.memory
+            .write(&mut self.store, msg_ptr as u32 as usize, msg)
+            .map_err(|err| Error::Wasm(err.to_string()))?;
+
+        let res_ptr: u64 = self.call_export(entry, msg_ptr)?;
+        let mut res = vec![0; (res_ptr >> 32) as usize];
+        let read = self
+            .memory
+            .read(&self.store, res_ptr as u32 as usize, &mut res)
+            .map_err(|err| Error::Wasm(err.to_string()));
+        let freed = self.deallocate(res_ptr);
+        read?;
+        freed?;
+        Ok(res)
+    }
+
+    fn deallocate(&mut self, ptr: u64) -> Result<(), Error> {
+        match self.call_export::<u64, i32>("deallocate", ptr)? {
+            0 => Ok(()),
+            code => Err(Error::InvalidPointer(code)),
+        }
+    }
+
+    fn call_export<P: WasmParams, R: WasmResults>(
+        &mut self,
+        name: &str,
+        params: P,
+    ) -> Result<R, Error> {
+        let func: TypedFunc<P, R> = self
+            .instance
+            .get_typed_func(&self.store, name)
+            .map_err(|_| Error::MissingExport(name.into()))?;
+        func.call(&mut self.store, params).map_err(|err| {
+            // A panic is reported right before the trap.
+            match self.store.data_mut().panic.take() {
+                Some(panic) => panic,
+                None => Error::Trap(err.to_string()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kelk_env::mock::{mock_context, MockBlockchain, MockStorageAPI};
+    use kelk_env::storage::{Storage, LAYOUT_VERSION, RESERVED_SIZE};
+
+    /// A minimal contract that echoes the process message into the storage
+    /// and returns the first four bytes of the storage for queries.
+    fn test_wasm(version: u32) -> Vec<u8> {
+        let wat = format!(
+            r#"(module
+                (import "zarb_v2" "write_storage" (func $write_storage (param i32 i32 i32) (result i32)))
+                (import "zarb_v2" "read_storage" (func $read_storage (param i32 i32 i32) (result i32)))
+                (import "zarb_v2" "report_panic" (func $report_panic (param i32 i32 i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "boomlib.rs")
+                (global $next (mut i32) (i32.const 1024))
+                (global $freed (export "freed") (mut i32) (i32.const 0))
+                (func $allocate (export "allocate") (param $len i32) (result i64)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (i64.or
+                        (i64.shl (i64.extend_i32_u (local.get $len)) (i64.const 32))
+                        (i64.extend_i32_u (local.get $ptr))))
+                (func (export "deallocate") (param i64) (result i32)
+                    (global.set $freed (i32.add (global.get $freed) (i32.const 1)))
+                    (i32.const 0))
+                (func (export "kelk_interface_version") (result i32)
+                    (i32.const {version}))
+                (func (export "process") (param $msg i64) (result i64)
+                    (drop (call $write_storage
+                        (i32.const 0)
+                        (i32.wrap_i64 (local.get $msg))
+                        (i32.wrap_i64 (i64.shr_u (local.get $msg) (i64.const 32)))))
+                    (local.get $msg))
+                (func (export "query") (param i64) (result i64)
+                    (local $res i64)
+                    (local.set $res (call $allocate (i32.const 4)))
+                    (drop (call $read_storage
+                        (i32.const 0)
+                        (i32.wrap_i64 (local.get $res))
+                        (i32.const 4)))
+                    (local.get $res))
+                (func (export "out_of_bounds") (param i64) (result i64)
+                    (i64.or (i64.shl (i64.const 4) (i64.const 32)) (i64.const 0x10000)))
+                (func (export "panic") (param i64) (result i64)
+                    (call $report_panic (i32.const 16) (i32.const 4) (i32.const 20) (i32.const 6) (i32.const 1) (i32.const 2))
+                    unreachable))"#
+        );
+        wat::parse_str(wat).unwrap()
+    }
+
+    fn test_contract() -> Contract<MockBlockchain> {
+        Contract::new(&test_wasm(ABI_VERSION), mock_context(64)).unwrap()
+    }
+
+    /// returns the number of the pointers that are freed by the contract
+    fn freed(contract: &Contract<MockBlockchain>) -> i32 {
+        contract
+            .instance
+            .get_global(&contract.store, "freed")
+            .unwrap()
+            .get(&contract.store)
+            .i32()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_call() {
+        let mut contract = test_contract();
+
+        assert_eq!(contract.process(b"abcd").unwrap(), b"abcd");
+        assert_eq!(contract.context().storage.api.read(0, 4).unwrap(), b"abcd");
+        assert_eq!(contract.query(&[]).unwrap(), b"abcd");
+        assert_eq!(contract.peak_memory(), None);
+        assert_eq!(freed(&contract), 4);
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut contract = test_contract();
+
+        assert_eq!(
+            contract.call("panic", &[]),
+            Err(Error::Panic {
+                msg: "boom".into(),
+                file: "lib.rs".into(),
+                line: 1,
+                column: 2,
+            })
+        );
+        // The message is freed after the trap.
+        assert_eq!(freed(&contract), 1);
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut contract = test_contract();
+
+        assert!(matches!(
+            contract.call("out_of_bounds", &[]),
+            Err(Error::Wasm(_))
+        ));
+        // Both the message and the result are freed.
+        assert_eq!(freed(&contract), 2);
+    }
+
+    #[test]
+    fn test_missing_export() {
+        let mut contract = test_contract();
+
+        assert_eq!(
+            contract.migrate(&[]),
+            Err(Error::MissingExport("migrate".into()))
+        );
+    }
+
+    #[test]
+    fn test_incompatible_version() {
+        assert_eq!(
+            Contract::new(&test_wasm(ABI_VERSION + 1), mock_context(64)).err(),
+            Some(Error::IncompatibleVersion(ABI_VERSION + 1))
+        );
+    }
+
+    /// Runs the calculator example. Build it before running the test:
+    /// `cargo build --release --target wasm32-unknown-unknown` in `examples/calculator`,
+    /// then `cargo test -p kelk-host -- --ignored`. The examples workflow runs it too.
+    #[test]
+    #[ignore]
+    fn test_calculator() {
+        let wasm = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../../examples/calculator/target/wasm32-unknown-unknown/release/calculator.wasm"
+        ))
+        .unwrap();
+        // The storage is not formatted, like the storage of a new contract on the chain.
+        let mut ctx = mock_context(0);
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 64);
+        ctx.storage = Storage::new(Box::new(api));
+        let mut contract = Contract::new(&wasm, ctx).unwrap();
+        assert_eq!(
+            contract.metadata().contract_name.as_deref(),
+            Some("calculator")
+        );
+
+        // ProcessMsg::Mul { a: 3, b: 2 } -> Ok(Response::default())
+        let res = contract.process(&[0x82, 0x02, 0x82, 0x03, 0x02]).unwrap();
+        assert_eq!(res, [0x82, 0x00, 0x84, 0xf6, 0x80, 0x80, 0x80]);
+        assert!(contract.peak_memory().unwrap() > 0);
+        assert_eq!(
+            contract.context().storage.layout_version().unwrap(),
+            LAYOUT_VERSION
+        );
+
+        // QueryMsg::LastResult {} -> Ok(QueryRsp::LastResult(6))
+        let res = contract.query(&[0x82, 0x00, 0x80]).unwrap();
+        assert_eq!(res, [0x82, 0x00, 0x82, 0x00, 0x81, 0x06]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Define the errors of running a contract.
+
+use std::fmt;
+
+/// The errors of loading and calling a contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The wasm module can't be loaded or instantiated
+    Wasm(String),
+    /// The contract doesn't export the function, or its signature is wrong
+    MissingExport(String),
+    /// The contract is built for another version of the host interface
+    IncompatibleVersion(u32),
+    /// The contract trapped
+    Trap(String),
+    /// The contract panicked and reported the panic before trapping
+    Panic {
+        /// The panic message
+        msg: String,
+        /// The source file where the panic occurred
+        file: String,
+        /// The line where the panic occurred
+        line: u32,
+        /// The column where the panic occurred
+        column: u32,
+    },
+    /// The contract refused to free the pointer with the given error code,
+    /// like `ERR_INVALID_POINTER`
+    InvalidPointer(i32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Wasm(msg) => write!(f, "wasm error: {}", msg),
+            Error::MissingExport(name) => write!(f, "missing export: {}", name),
+            Error::IncompatibleVersion(version) => {
+                write!(f, "incompatible interface version: {}", version)
+            }
+            Error::Trap(msg) => write!(f, "contract trapped: {}", msg),
+            Error::Panic {
+                msg,
+                file,
+                line,
+                column,
+            } => write!(
+                f,
+                "contract panicked at {}:{}:{}: {}",
+                file, line, column, msg
+            ),
+            Error::InvalidPointer(code) => write!(f, "invalid pointer, code: {}", code),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<wasmi::Error> for Error {
+    fn from(err: wasmi::Error) -> Self {
+        Error::Wasm(err.to_string())
+    }
+}
@@ -0,0 +1,278 @@
+//! The host functions that are imported by the contracts
+//!
+//! The functions are defined in the [`IMPORT_MODULE`] module and they forward the calls
+//! to the context of the contract. Host errors are returned to the contract as error codes,
+//! but generic errors and invalid memory accesses trap.
+
+use kelk_env::address::Address;
+use kelk_env::amount::Amount;
+use kelk_env::blockchain::Blockchain;
+use kelk_env::context::OwnedContext;
+use kelk_env::crypto::{
+    BLS_PUBLIC_KEY_SIZE, BLS_SIGNATURE_SIZE, ED25519_PUBLIC_KEY_SIZE, ED25519_SIGNATURE_SIZE,
+};
+use kelk_env::error::{Error as EnvError, ERR_INVALID_SIGNATURE};
+use kelk_env::wasm_metadata::IMPORT_MODULE;
+use wasmi::{Caller, Error, Extern, Linker, Memory};
+
+/// The data of the store that the host functions can access
+pub(crate) struct HostState<B: Blockchain> {
+    /// The context of the contract
+    pub ctx: OwnedContext<B>,
+    /// The panic that is reported by the contract
+    pub panic: Option<crate::Error>,
+}
+
+/// defines the host functions in the linker
+pub(crate) fn link<B: Blockchain + 'static>(
+    linker: &mut Linker<HostState<B>>,
+) -> Result<(), Error> {
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "write_storage",
+        |mut caller: Caller<'_, HostState<B>>, offset: u32, ptr: u32, len: u32| {
+            let data = read(&mut caller, ptr, len)?;
+            code(caller.data().ctx.storage.api.write(offset, &data))
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "read_storage",
+        |mut caller: Caller<'_, HostState<B>>, offset: u32, ptr: u32, len: u32| match caller
+            .data()
+            .ctx
+            .storage
+            .api
+            .read(offset, len)
+        {
+            Ok(data) => write(&mut caller, ptr, &data).map(|_| 0),
+            Err(err) => code(Err(err)),
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "query_contract",
+        |mut caller: Caller<'_, HostState<B>>,
+         addr_ptr: u32,
+         msg_ptr: u32,
+         msg_len: u32,
+         res_ptr: u32| {
+            let addr = read_address(&mut caller, addr_ptr)?;
+            let msg = read(&mut caller, msg_ptr, msg_len)?;
+            let res = caller.data().ctx.blockchain.query_contract(&addr, &msg);
+            write_result(&mut caller, res, res_ptr)
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "process_contract",
+        |mut caller: Caller<'_, HostState<B>>,
+         addr_ptr: u32,
+         msg_ptr: u32,
+         msg_len: u32,
+         res_ptr: u32| {
+            let addr = read_address(&mut caller, addr_ptr)?;
+            let msg = read(&mut caller, msg_ptr, msg_len)?;
+            let res = caller.data().ctx.blockchain.process_contract(&addr, &msg);
+            write_result(&mut caller, res, res_ptr)
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "get_attached_amount",
+        |mut caller: Caller<'_, HostState<B>>, amount_ptr: u32| match caller
+            .data()
+            .ctx
+            .blockchain
+            .attached_amount()
+        {
+            Ok(amount) => write(&mut caller, amount_ptr, &amount.to_be_bytes()).map(|_| 0),
+            Err(err) => code(Err(err)),
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "get_balance",
+        |mut caller: Caller<'_, HostState<B>>, amount_ptr: u32| match caller
+            .data()
+            .ctx
+            .blockchain
+            .balance()
+        {
+            Ok(amount) => write(&mut caller, amount_ptr, &amount.to_be_bytes()).map(|_| 0),
+            Err(err) => code(Err(err)),
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "transfer",
+        |mut caller: Caller<'_, HostState<B>>, addr_ptr: u32, amount_ptr: u32| {
+            let addr = read_address(&mut caller, addr_ptr)?;
+            let amount = read_array::<16, _>(&mut caller, amount_ptr)?;
+            code(
+                caller
+                    .data()
+                    .ctx
+                    .blockchain
+                    .transfer(&addr, Amount::from_be_bytes(amount)),
+            )
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "gas_remaining",
+        |caller: Caller<'_, HostState<B>>| caller.data().ctx.blockchain.gas_remaining(),
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "consume_gas",
+        |caller: Caller<'_, HostState<B>>, amount: u64| {
+            code(caller.data().ctx.blockchain.consume_gas(amount))
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "block_number",
+        |caller: Caller<'_, HostState<B>>| caller.data().ctx.blockchain.block_number(),
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "hash_blake2b_256",
+        |mut caller: Caller<'_, HostState<B>>, data_ptr: u32, data_len: u32, hash_ptr: u32| {
+            let data = read(&mut caller, data_ptr, data_len)?;
+            match caller.data().ctx.crypto.blake2b_256(&data) {
+                Ok(hash) => write(&mut caller, hash_ptr, &hash).map(|_| 0),
+                Err(err) => code(Err(err)),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "hash_sha256",
+        |mut caller: Caller<'_, HostState<B>>, data_ptr: u32, data_len: u32, hash_ptr: u32| {
+            let data = read(&mut caller, data_ptr, data_len)?;
+            match caller.data().ctx.crypto.sha256(&data) {
+                Ok(hash) => write(&mut caller, hash_ptr, &hash).map(|_| 0),
+                Err(err) => code(Err(err)),
+            }
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "verify_ed25519",
+        |mut caller: Caller<'_, HostState<B>>,
+         pk_ptr: u32,
+         msg_ptr: u32,
+         msg_len: u32,
+         sig_ptr: u32| {
+            let pk = read_array::<ED25519_PUBLIC_KEY_SIZE, _>(&mut caller, pk_ptr)?;
+            let msg = read(&mut caller, msg_ptr, msg_len)?;
+            let sig = read_array::<ED25519_SIGNATURE_SIZE, _>(&mut caller, sig_ptr)?;
+            verify_code(caller.data().ctx.crypto.verify_ed25519(&pk, &msg, &sig))
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "verify_bls",
+        |mut caller: Caller<'_, HostState<B>>,
+         pk_ptr: u32,
+         msg_ptr: u32,
+         msg_len: u32,
+         sig_ptr: u32| {
+            let pk = read_array::<BLS_PUBLIC_KEY_SIZE, _>(&mut caller, pk_ptr)?;
+            let msg = read(&mut caller, msg_ptr, msg_len)?;
+            let sig = read_array::<BLS_SIGNATURE_SIZE, _>(&mut caller, sig_ptr)?;
+            verify_code(caller.data().ctx.crypto.verify_bls(&pk, &msg, &sig))
+        },
+    )?;
+    linker.func_wrap(
+        IMPORT_MODULE,
+        "report_panic",
+        |mut caller: Caller<'_, HostState<B>>,
+         msg_ptr: u32,
+         msg_len: u32,
+         file_ptr: u32,
+         file_len: u32,
+         line: u32,
+         column: u32| {
+            let msg = read(&mut caller, msg_ptr, msg_len)?;
+            let file = read(&mut caller, file_ptr, file_len)?;
+            caller.data_mut().panic = Some(crate::Error::Panic {
+                msg: String::from_utf8_lossy(&msg).into_owned(),
+                file: String::from_utf8_lossy(&file).into_owned(),
+                line,
+                column,
+            });
+            Ok(())
+        },
+    )?;
+    Ok(())
+}
+
+/// converts the result of the context to the error code that is returned to the contract
+fn code(res: Result<(), EnvError>) -> Result<i32, Error> {
+    match res {
+        Ok(()) => Ok(0),
+        Err(EnvError::HostError(code)) => Ok(code),
+        Err(err) => Err(Error::new(err.to_string())),
+    }
+}
+
+fn verify_code(res: Result<bool, EnvError>) -> Result<i32, Error> {
+    match res {
+        Ok(true) => Ok(0),
+        Ok(false) => Ok(ERR_INVALID_SIGNATURE),
+        Err(err) => code(Err(err)),
+    }
+}
+
+fn memory<T>(caller: &Caller<'_, T>) -> Result<Memory, Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| Error::new("missing memory export"))
+}
+
+pub(crate) fn read<T>(caller: &mut Caller<'_, T>, ptr: u32, len: u32) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; len as usize];
+    memory(caller)?.read(&*caller, ptr as usize, &mut buf)?;
+    Ok(buf)
+}
+
+fn read_array<const N: usize, T>(caller: &mut Caller<'_, T>, ptr: u32) -> Result<[u8; N], Error> {
+    let mut buf = [0; N];
+    memory(caller)?.read(&*caller, ptr as usize, &mut buf)?;
+    Ok(buf)
+}
+
+fn read_address<T>(caller: &mut Caller<'_, T>, ptr: u32) -> Result<Address, Error> {
+    let buf = read_array::<{ kelk_env::address::ADDRESS_SIZE }, _>(caller, ptr)?;
+    Address::from_bytes(&buf).map_err(|_| Error::new("invalid address"))
+}
+
+fn write<T>(caller: &mut Caller<'_, T>, ptr: u32, data: &[u8]) -> Result<(), Error> {
+    memory(caller)?.write(&mut *caller, ptr as usize, data)?;
+    Ok(())
+}
+
+/// copies the result of a nested call into a buffer allocated by the contract
+/// and writes the pointer of the buffer at `res_ptr`.
+fn write_result<T>(
+    caller: &mut Caller<'_, T>,
+    res: Result<Vec<u8>, EnvError>,
+    res_ptr: u32,
+) -> Result<i32, Error> {
+    let data = match res {
+        Ok(data) => data,
+        Err(err) => return code(Err(err)),
+    };
+    let allocate = caller
+        .get_export("allocate")
+        .and_then(Extern::into_func)
+        .ok_or_else(|| Error::new("missing allocate export"))?
+        .typed::<u32, u64>(&*caller)?;
+    let ptr = allocate.call(&mut *caller, data.len() as u32)?;
+    write(caller, ptr as u32, &data)?;
+    write(caller, res_ptr, &ptr.to_le_bytes())?;
+    Ok(0)
+}
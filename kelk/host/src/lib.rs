@@ -0,0 +1,27 @@
+//! Kelk-host runs the compiled contracts locally, like Tanour (Wasm executor) in Zarb blockchain.
+//!
+//! It loads the wasm file of a contract with an embedded wasm runtime and provides
+//! the host functions that the contract imports. The host functions are backed by
+//! a kelk-env context, like the mocked context, so the integration tests can run
+//! the actual wasm artifact of a contract and check its storage:
+//!
+//! ```no_run
+//! # use kelk_env::mock::mock_context;
+//! # use kelk_host::Contract;
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let msg = [0x82, 0x02, 0x82, 0x03, 0x02];
+//! let wasm = std::fs::read("calculator.wasm")?;
+//! let mut contract = Contract::new(&wasm, mock_context(1024))?;
+//! let res = contract.process(&msg)?;
+//! # Ok(())
+//! # }
+//! ```
+
+#![deny(missing_docs)]
+
+mod contract;
+mod error;
+mod imports;
+
+pub use crate::contract::Contract;
+pub use crate::error::Error;
@@ -158,7 +158,11 @@ where
 
 #[cfg(test)]
 mod tests {
-    use kelk_env::mock::mock_storage;
+    use alloc::boxed::Box;
+    use alloc::string::ToString;
+    use kelk_env::error::Error as EnvError;
+    use kelk_env::mock::{mock_storage, MockStorageAPI};
+    use kelk_env::storage::RESERVED_SIZE;
 
     use super::*;
     use core::mem::size_of;
@@ -231,4 +235,25 @@ mod tests {
         assert_eq!(None, bst.insert(4, 4).unwrap());
         assert!(bst.insert(5, 5).is_err());
     }
+
+    #[test]
+    fn test_failed_write() {
+        let api = MockStorageAPI::new(RESERVED_SIZE as usize + 1024);
+        let faults = api.faults();
+        let storage = Storage::new(Box::new(api));
+        let mut bst = StorageBST::<i32, i32>::create(&storage, 0, 16).unwrap();
+        bst.insert(1, 10).unwrap();
+
+        // The header and the parent node are updated, but writing the new node fails.
+        faults.fail_nth_write(3, 1);
+        assert!(matches!(
+            bst.insert(2, 20),
+            Err(Error::KelkError(EnvError::HostError(1)))
+        ));
+
+        faults.fail_reads(RESERVED_SIZE..RESERVED_SIZE + 1024, 2);
+        let err = bst.find(&1).unwrap_err();
+        assert!(matches!(err, Error::KelkError(EnvError::HostError(2))));
+        assert_eq!(err.to_string(), "host error");
+    }
 }
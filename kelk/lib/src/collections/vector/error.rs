@@ -4,8 +4,8 @@ use core::fmt::{self, Debug};
 
 /// A general list of Storage Binary Tree error
 pub enum Error {
-    /// Kelk error
-    KelkError,
+    /// Kelk error, with the error of the storage
+    KelkError(kelk_env::error::Error),
 
     /// Invalid offset
     InvalidOffset(u32),
@@ -17,7 +17,7 @@ pub enum Error {
 impl Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::KelkError => f.debug_struct("KelkError").finish(),
+            Error::KelkError(error) => f.debug_tuple("KelkError").field(error).finish(),
 
             Error::InvalidOffset(offset) => f
                 .debug_struct("InvalidOffset")
@@ -31,7 +31,8 @@ impl Debug for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Error::KelkError => write!(f, "kelk error"),
+            // The error of the storage is the source.
+            Error::KelkError(_) => write!(f, "kelk error"),
             Error::InvalidOffset(offset) => write!(f, "invalid offset: {:?}", offset),
             Error::OutOfCapacity => write!(f, "Capacity is full"),
         }
@@ -39,7 +40,42 @@ impl fmt::Display for Error {
 }
 
 impl From<kelk_env::error::Error> for Error {
-    fn from(_error: kelk_env::error::Error) -> Self {
-        Error::KelkError
+    fn from(error: kelk_env::error::Error) -> Self {
+        Error::KelkError(error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::KelkError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use kelk_env::error::Error as EnvError;
+
+    #[test]
+    fn test_display() {
+        let err = Error::from(EnvError::HostError(1));
+        assert!(matches!(err, Error::KelkError(EnvError::HostError(1))));
+        assert_eq!(err.to_string(), "kelk error");
+        assert_eq!(Error::InvalidOffset(2).to_string(), "invalid offset: 2");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_source() {
+        use std::error::Error as _;
+
+        let err = Error::from(EnvError::HostError(1));
+        assert_eq!(err.source().unwrap().to_string(), "host error code: 1");
+        assert!(Error::OutOfCapacity.source().is_none());
     }
 }
@@ -27,3 +27,6 @@ pub mod collections;
 pub mod types;
 
 pub extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
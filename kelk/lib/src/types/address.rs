@@ -1,56 +1,20 @@
 //! Address type for representing actor address
-use core::result::Result;
+//!
+//! The address is defined by kelk-env, so the contracts and the host APIs share the same type.
 
-/// Address type
-const ADDRESS_SIZE: usize = 21;
-
-/// Address type in Zarb blockchain
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Address([u8; ADDRESS_SIZE]);
-
-// impl Address {
-//     pub fn from_bytes(buf: &[u8]) -> Result<Self> {
-//         let data = buf.try_into().map_err(|_| Error::InvalidLength {
-//             expected: ADDRESS_SIZE,
-//             found: buf.len(),
-//         })?;
-//         Ok(Self(data))
-//     }
-
-//     pub fn as_bytes(&self) -> &[u8; ADDRESS_SIZE] {
-//         &self.0
-//     }
-// }
-
-impl<C> minicbor::Encode<C> for Address {
-    fn encode<W>(
-        &self,
-        e: &mut minicbor::Encoder<W>,
-        _ctx: &mut C,
-    ) -> core::result::Result<(), minicbor::encode::Error<W::Error>>
-    where
-        W: minicbor::encode::Write,
-    {
-        e.bytes(self.0.as_ref())?;
-        Ok(())
-    }
-}
-
-impl<'a, C> minicbor::Decode<'a, C> for Address {
-    fn decode(
-        d: &mut minicbor::Decoder<'a>,
-        _ctx: &mut C,
-    ) -> core::result::Result<Address, minicbor::decode::Error> {
-        let buf = d.bytes()?;
-        let data = buf
-            .try_into()
-            .map_err(|_| minicbor::decode::Error::message("invalid data"))?;
-
-        Ok(Address(data))
-    }
-}
+pub use kelk_env::address::{Address, ADDRESS_SIZE};
 
 #[cfg(test)]
 mod tests {
-    // pub fn generate_test_address() -> Address {}
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_encoding() {
+        // The address is encoded by the minicbor of the workspace.
+        let addr = Address::from([7; ADDRESS_SIZE]);
+        let mut buf = Vec::new();
+        minicbor::encode(addr, &mut buf).unwrap();
+        assert_eq!(minicbor::decode::<Address>(&buf).unwrap(), addr);
+    }
 }